@@ -2,7 +2,9 @@ use std::{path::{Path, PathBuf}, process::exit};
 
 use clap::ArgMatches;
 
-use crate::{cli::CommandProcessor, util, zip::{ZipFile, mem_map::EncryptionMethod, options::ExtractOptions}};
+use ruzito::{ExtractOptions, ZipFile};
+
+use crate::{cli::{CommandProcessor, progress::TerminalProgress}, util};
 
 
 pub struct ExtractCommand;
@@ -21,13 +23,13 @@ impl CommandProcessor for ExtractCommand {
                 Ok(path_buf) => path_buf,
                 Err(err) => {
                     eprintln!("An error occured while canonicalizing the given zip path. Error: {}", err);
-                    return;
+                    exit(util::EXIT_FAILURE);
                 }
             };
 
             if !relative_path.exists() {
                 eprintln!("Given file path does not exist!");
-                return;
+                exit(util::EXIT_INVALID_ARGUMENTS);
             }
 
             relative_path
@@ -41,10 +43,12 @@ impl CommandProcessor for ExtractCommand {
         let mut zip_file = match zip_file {
             Ok(zip_file) => zip_file,
             Err(err) => {
-                eprintln!("An error occured while extracting the ZIP file! Error: {:?}", err);
-                exit(-1);
+                eprintln!("An error occured while reading the ZIP file! Error: {}", err);
+                exit(util::exit_code(&err));
             }
         };
+        util::raise_decoder_limits(&mut zip_file, matches);
+
         let destination_path = matches.value_of("dest_path")
             .map(|path| PathBuf::new().join(path));
         let destination_path = match destination_path {
@@ -53,48 +57,65 @@ impl CommandProcessor for ExtractCommand {
                 Some(file_name) => {
                     let mut path_buf = PathBuf::new().join(file_name);
                     
-                    if let Some(_) = path_buf.extension() {
+                    if path_buf.extension().is_some() {
                         path_buf.set_extension("");
                     }
 
                     if !path_buf.exists() {
                         if let Err(err) = std::fs::create_dir_all(path_buf.as_path()) {
                             eprintln!("An error occured while creating the destination path folder. Error: {}", err);
-                            return;
+                            exit(util::EXIT_FAILURE);
                         }
                     }
                     path_buf
                 },
                 None => {
                     eprintln!("An error occured while generating the destination path for extraction.");
-                    return;
+                    exit(util::EXIT_INVALID_ARGUMENTS);
                 }
             })
         };
 
         if destination_path.is_file() {
             eprintln!("Destination path of the extracted files cannot be a file path!");
-            return;
+            exit(util::EXIT_INVALID_ARGUMENTS);
         }
 
-        let zip_password = matches.value_of("password")
-            .map(|pass_str| String::from(pass_str));
-        let zip_password = match zip_password {
-            Some(pass) => Some(pass),
-            None => if zip_file.file_encryption_method() != &EncryptionMethod::NoEncryption {
-                match util::read_pass() {
-                    Ok(pass) => Some(pass),
-                    Err(_) => None
-                }
-            } else {
-                None
+        let passwords = match util::candidate_passwords(matches) {
+            Ok(passwords) => passwords,
+            Err(err) => {
+                eprintln!("An error occured while reading the password. Error: {}", err);
+                exit(util::EXIT_INVALID_ARGUMENTS);
             }
         };
-        zip_file.extract_all(ExtractOptions::new(matches.is_present("verbose"),
-             destination_path.as_path(),
-             zip_password,
-             zip_file.zip_file_path().clone()
-            ));
+        let threads = match matches.value_of("jobs").map(|jobs| jobs.parse::<usize>()) {
+            Some(Ok(jobs)) if jobs > 0 => jobs,
+            Some(_) => {
+                eprintln!("Number of jobs must be a positive number. Exiting...");
+                exit(util::EXIT_INVALID_ARGUMENTS);
+            },
+            None => 1
+        };
+
+        let total_bytes = zip_file.iter()
+            .map(|item| item.uncompressed_size() as u64)
+            .sum();
+        let progress = TerminalProgress::new(Some(total_bytes), matches.is_present("verbose"));
+        let mut extract_options = ExtractOptions::new(destination_path.as_path(),
+             passwords,
+             Some(&util::TerminalPasswordPrompt),
+             threads
+            );
+        extract_options.set_progress_observer(&progress);
+        extract_options.set_cancellation_token(util::cancel_on_interrupt());
+
+        let extract_result = zip_file.extract_all(extract_options);
+        progress.finish();
+
+        if let Err(err) = extract_result {
+            eprintln!("An error occured while extracting the ZIP file! Error: {}", err);
+            exit(util::exit_code(&err));
+        }
 
     }
 
@@ -3,7 +3,9 @@ use std::path::{Path, PathBuf};
 use clap::ArgMatches;
 use cli_table::{Cell, CellStruct, Table, format::Justify, print_stdout};
 
-use crate::{cli::CommandProcessor, zip::{ZipFile, mem_map::{EncryptionMethod, CompressionMethod}}};
+use ruzito::{CompressionMethod, EncryptionMethod, ZipFile, ZipItem};
+
+use crate::{cli::CommandProcessor, util};
 
 pub struct ListCommand;
 
@@ -23,13 +25,13 @@ impl CommandProcessor for ListCommand {
                 Ok(path_buf) => path_buf,
                 Err(err) => {
                     eprintln!("An error occured while canonicalizing the given zip path. Error: {}", err);
-                    return;
+                    exit(util::EXIT_FAILURE);
                 }
             };
 
             if !relative_path.exists() {
                 eprintln!("Given file path does not exist!");
-                return;
+                exit(util::EXIT_INVALID_ARGUMENTS);
             }
 
             relative_path
@@ -41,8 +43,8 @@ impl CommandProcessor for ListCommand {
         let zip_file = match ZipFile::new(file_path) {
             Ok(zip_file) => zip_file,
             Err(err) => {
-                eprintln!("An error occured while extracting the ZIP file! Error: {:?}", err);
-                exit(-1)
+                eprintln!("An error occured while reading the ZIP file! Error: {}", err);
+                exit(util::exit_code(&err))
             }
         };
 
@@ -51,20 +53,15 @@ impl CommandProcessor for ListCommand {
                 let compression_perc = if item.uncompressed_size() > 0 && item.compression_method() != CompressionMethod::NoCompression {
                     let compressed_size = item.compressed_size() as f32;
                     let uncompressed_size = item.uncompressed_size() as f32;
-                    let perc = ((compressed_size / uncompressed_size) * 100.0) as f32;
-                    format!("({:.1}%)", 100 as f32 - perc)
+                    let perc = (compressed_size / uncompressed_size) * 100.0 ;
+                    format!("({:.1}%)", 100_f32 - perc)
                 }
                 else { String::from("") };
-                let file_protected = if item.encryption_method() == EncryptionMethod::NoEncryption {
-                    "No"
-                } else {
-                    "Yes"
-                };
                 vec![
                     item.item_path().cell(),
                     format!("{:?} {}", item.compression_method(), compression_perc).cell(),
                     item.compressed_size().cell().justify(Justify::Right),
-                    file_protected.cell(),
+                    encryption_scheme(item).cell(),
                     item.uncompressed_size().cell().justify(Justify::Right),
                     format!("{}", item.modified_date_time()).cell()
                 ]}
@@ -75,15 +72,27 @@ impl CommandProcessor for ListCommand {
               "Item".cell(),
               "Compression".cell(),
               "Compressed Size".cell(),
-              "Password Protected".cell(),
+              "Encryption".cell(),
               "File Size".cell(),
               "Modified Date".cell()
             ]);
           if let Err(err) = print_stdout(list_table) {                            
               eprintln!("An error occured while creating the table. {}", err);
-              exit(-1);
+              exit(util::EXIT_FAILURE);
           }
           println!("\n{} files/directories listed.\n", zip_file.file_count());
 
     }
 }
+
+fn encryption_scheme(item: &ZipItem) -> String {
+    match item.encryption_method() {
+        EncryptionMethod::NoEncryption => String::from("None"),
+        EncryptionMethod::ZipCrypto => String::from("ZipCrypto"),
+        EncryptionMethod::StrongEncryption => String::from("Strong Encryption (unsupported)"),
+        EncryptionMethod::WinZipAesEncryption => match item.aes_extra_field() {
+            Some(aes_extra_field) => format!("AES-{} (AE-{})", aes_extra_field.strength().key_size() * 8, aes_extra_field.vendor_version()),
+            None => String::from("AES (unsupported)")
+        }
+    }
+}
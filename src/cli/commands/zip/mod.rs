@@ -1,9 +1,25 @@
-use clap::{Arg, ArgMatches, SubCommand};
+use clap::{Arg, ArgGroup, ArgMatches, SubCommand};
 
 use crate::cli::{CommandProcessor, RuzitoSubCommand};
 
+pub const COMPRESSION_LEVEL_ARGS: [(&str, &str, &str); 10] = [
+    ("level_0", "0", "Store the items without compressing them"),
+    ("level_1", "1", "Compress faster"),
+    ("level_2", "2", "Compression level 2"),
+    ("level_3", "3", "Compression level 3"),
+    ("level_4", "4", "Compression level 4"),
+    ("level_5", "5", "Compression level 5"),
+    ("level_6", "6", "Compression level 6"),
+    ("level_7", "7", "Compression level 7"),
+    ("level_8", "8", "Compression level 8"),
+    ("level_9", "9", "Compress better (default)")
+];
+
 mod extract;
 mod list;
+mod recrypt;
+mod test;
+#[allow(clippy::module_inception)]
 mod zip;
 
 pub struct ZipSubCommand {
@@ -18,6 +34,10 @@ impl ZipSubCommand {
             commands: vec![
                 Box::new(extract::ExtractCommand),
                 Box::new(list::ListCommand),
+                Box::new(recrypt::RekeyCommand),
+                Box::new(recrypt::EncryptArchiveCommand),
+                Box::new(recrypt::DecryptArchiveCommand),
+                Box::new(test::TestCommand),
                 Box::new(zip::ZipCommand)
             ]
         }
@@ -47,6 +67,13 @@ impl RuzitoSubCommand for ZipSubCommand {
                 .long("encrypt") 
                 .help("Encrypt the generated zip file")
                 .case_insensitive(true))
+            .arg(Arg::with_name("encryption")
+                .long("encryption")
+                .help("Encryption of the generated zip file, implies --encrypt. Defaults to zipcrypto, which is weak, prefer aes256.")
+                .case_insensitive(true)
+                .takes_value(true)
+                .possible_values(&["aes256", "aes128", "zipcrypto"])
+                .value_name("ENCRYPTION"))
             .arg(Arg::with_name("dest_path")
                 .short("d")
                 .long("destination-path") 
@@ -61,6 +88,51 @@ impl RuzitoSubCommand for ZipSubCommand {
                 .takes_value(true)
                 .value_name("PASSWORD")
                 .case_insensitive(true))
+            .arg(Arg::with_name("password_env")
+                .long("password-env")
+                .help("Reads the password from the given environment variable instead of the command line.")
+                .takes_value(true)
+                .value_name("VAR"))
+            .arg(Arg::with_name("password_fd")
+                .long("password-fd")
+                .help("Reads passwords, one per line, from the given file descriptor (Unix only).")
+                .takes_value(true)
+                .value_name("N"))
+            .arg(Arg::with_name("password_file")
+                .long("password-file")
+                .help("A file of candidate passwords, one per line, tried on every encrypted item before asking for a password. Zipping uses the first one.")
+                .takes_value(true)
+                .value_name("PATH"))
+            .arg(Arg::with_name("encrypt_only")
+                .long("encrypt-only")
+                .help("Comma separated patterns of the items to encrypt while zipping, e.g. *.key,secret/*. Implies --encrypt.")
+                .takes_value(true)
+                .value_name("PATTERNS"))
+            .arg(Arg::with_name("rekey")
+                .long("rekey")
+                .help("Re-encrypts the files of the given ZIP file with a new password, and with --encryption if given, without recompressing them")
+                .takes_value(true)
+                .value_name("ZIP_FILE"))
+            .arg(Arg::with_name("encrypt_archive")
+                .long("encrypt-archive")
+                .help("Encrypts the files of the given ZIP file, with --encryption if given, without recompressing them")
+                .takes_value(true)
+                .value_name("ZIP_FILE"))
+            .arg(Arg::with_name("decrypt_archive")
+                .long("decrypt-archive")
+                .help("Decrypts the files of the given ZIP file without recompressing them")
+                .takes_value(true)
+                .value_name("ZIP_FILE"))
+            .arg(Arg::with_name("new_password")
+                .long("new-password")
+                .help("The new password used with --rekey.")
+                .takes_value(true)
+                .value_name("PASSWORD"))
+            .arg(Arg::with_name("new_password_env")
+                .long("new-password-env")
+                .help("Reads the new password used with --rekey from the given environment variable.")
+                .takes_value(true)
+                .value_name("VAR"))
             .arg(Arg::with_name("list")
                 .short("l")
                 .long("list")
@@ -68,6 +140,13 @@ impl RuzitoSubCommand for ZipSubCommand {
                 .case_insensitive(true)
                 .takes_value(true)
                 .value_name("ZIP_FILE"))
+            .arg(Arg::with_name("test")
+                .short("t")
+                .long("test")
+                .help("Tests the files inside of the ZIP file by decompressing them and checking their CRC32")
+                .case_insensitive(true)
+                .takes_value(true)
+                .value_name("ZIP_FILE"))
             .arg(Arg::with_name("zip")
                 .short("z")
                 .long("zip")
@@ -82,6 +161,54 @@ impl RuzitoSubCommand for ZipSubCommand {
                 .case_insensitive(true)
                 .takes_value(true)
                 .value_name("ZIP_FILE_NAME"))
+            .arg(Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .help("Number of files extracted in parallel. Defaults to 1.")
+                .case_insensitive(true)
+                .takes_value(true)
+                .value_name("N"))
+            .args(&COMPRESSION_LEVEL_ARGS.iter()
+                .map(|(name, level, help)| Arg::with_name(name)
+                    .short(level)
+                    .help(help))
+                .collect::<Vec<_>>())
+            .group(ArgGroup::with_name("compression_level")
+                .args(&COMPRESSION_LEVEL_ARGS.iter().map(|(name, _, _)| *name).collect::<Vec<_>>()))
+            .arg(Arg::with_name("method")
+                .long("method")
+                .help("Compression method of the zipped files. By default files are deflated unless deflating does not make them smaller or they are already compressed.")
+                .case_insensitive(true)
+                .takes_value(true)
+                .possible_values(&["store", "deflate", "bzip2", "zstd", "lzma", "xz"])
+                .conflicts_with("level_0")
+                .value_name("METHOD"))
+            .arg(Arg::with_name("zstd_level")
+                .long("zstd-level")
+                .help("Compression level used with --method zstd, from 1 to 22. Defaults to 3.")
+                .takes_value(true)
+                .value_name("LEVEL"))
+            .arg(Arg::with_name("zstd_long")
+                .long("zstd-long")
+                .help("Enables long distance matching with --method zstd, using a window of 2^N bytes (N from 10 to 31). Extracting and testing accept windows above 2^27 bytes only with it.")
+                .takes_value(true)
+                .value_name("N"))
+            .arg(Arg::with_name("lzma_memory_limit")
+                .long("lzma-memory-limit")
+                .help("Lets extracting and testing use up to N MiB to decode an LZMA or XZ item, for items with large dictionaries. Defaults to 128.")
+                .takes_value(true)
+                .value_name("N"))
+            .arg(Arg::with_name("store_extensions")
+                .long("store-extensions")
+                .help("Comma separated extensions of the files that are stored without compression, e.g. jpg,png,zip. Defaults to common compressed formats.")
+                .takes_value(true)
+                .value_name("EXTENSIONS"))
+            .arg(Arg::with_name("threads")
+                .long("threads")
+                .help("Number of threads used to compress the items while zipping. Defaults to 1.")
+                .case_insensitive(true)
+                .takes_value(true)
+                .value_name("N"))
     }
 
     fn run_command_processes(&self, matches: &ArgMatches) {
@@ -0,0 +1,150 @@
+use std::{path::PathBuf, process::exit};
+
+use clap::ArgMatches;
+
+use ruzito::{EncryptionMethod, Password, RecryptOptions, ZipEncryption, ZipFile};
+
+use crate::{cli::{CommandProcessor, progress::TerminalProgress}, util};
+
+use super::zip::encryption_from_name;
+
+pub struct RekeyCommand;
+pub struct EncryptArchiveCommand;
+pub struct DecryptArchiveCommand;
+
+impl CommandProcessor for RekeyCommand {
+    fn command_name(&self) -> &str {
+        "rekey"
+    }
+
+    fn process_command(&self, matches: &ArgMatches) {
+        let zip_file = open_zip_file(matches, self.command_name());
+
+        // Keeps the encryption of the archive unless another one is given.
+        let encryption = match encryption_option(matches) {
+            Some(encryption) => encryption,
+            None => archive_encryption(&zip_file)
+        };
+        let encryption = match encryption {
+            Some(encryption) => encryption,
+            None => {
+                eprintln!("The ZIP file is not encrypted, use --encrypt-archive to encrypt it. Exiting...");
+                exit(util::EXIT_INVALID_ARGUMENTS);
+            }
+        };
+
+        let passwords = candidate_passwords(matches);
+        let new_password = match util::new_password(matches) {
+            Ok(new_password) => new_password,
+            Err(err) => {
+                eprintln!("An error occured while reading the new password. Error: {}", err);
+                exit(util::EXIT_INVALID_ARGUMENTS);
+            }
+        };
+
+        recrypt(matches, &zip_file, passwords, Some(encryption), Some(new_password));
+    }
+}
+
+impl CommandProcessor for EncryptArchiveCommand {
+    fn command_name(&self) -> &str {
+        "encrypt_archive"
+    }
+
+    fn process_command(&self, matches: &ArgMatches) {
+        let zip_file = open_zip_file(matches, self.command_name());
+        let encryption = encryption_option(matches).unwrap_or(Some(ZipEncryption::ZipCrypto));
+
+        // The given password is the one the files are encrypted with, the archive has none yet.
+        let new_password = match candidate_passwords(matches).into_iter().next() {
+            Some(new_password) => new_password,
+            None => match util::read_new_pass("Enter password: ") {
+                Ok(new_password) => new_password,
+                Err(err) => {
+                    eprintln!("{} Exiting...", err);
+                    exit(util::EXIT_INVALID_ARGUMENTS);
+                }
+            }
+        };
+
+        recrypt(matches, &zip_file, Vec::new(), encryption, Some(new_password));
+    }
+}
+
+impl CommandProcessor for DecryptArchiveCommand {
+    fn command_name(&self) -> &str {
+        "decrypt_archive"
+    }
+
+    fn process_command(&self, matches: &ArgMatches) {
+        let zip_file = open_zip_file(matches, self.command_name());
+        let passwords = candidate_passwords(matches);
+
+        recrypt(matches, &zip_file, passwords, None, None);
+    }
+}
+
+fn open_zip_file(matches: &ArgMatches, command_name: &str) -> ZipFile {
+    match ZipFile::new(PathBuf::from(matches.value_of(command_name).unwrap())) {
+        Ok(zip_file) => zip_file,
+        Err(err) => {
+            eprintln!("An error occured while reading the ZIP file! Error: {}", err);
+            exit(util::exit_code(&err));
+        }
+    }
+}
+
+// None if --encryption is not given.
+fn encryption_option(matches: &ArgMatches) -> Option<Option<ZipEncryption>> {
+    let encryption_name = matches.value_of("encryption")?;
+
+    match encryption_from_name(encryption_name) {
+        Some(encryption) => Some(Some(encryption)),
+        None => {
+            eprintln!("Unsupported encryption {}. Exiting...", encryption_name);
+            exit(util::EXIT_INVALID_ARGUMENTS);
+        }
+    }
+}
+
+fn archive_encryption(zip_file: &ZipFile) -> Option<ZipEncryption> {
+    zip_file.iter()
+        .find_map(|item| match item.encryption_method() {
+            EncryptionMethod::ZipCrypto => Some(ZipEncryption::ZipCrypto),
+            EncryptionMethod::WinZipAesEncryption => item.aes_extra_field()
+                .map(|aes_extra_field| ZipEncryption::WinZipAes(aes_extra_field.strength())),
+            _ => None
+        })
+}
+
+fn candidate_passwords(matches: &ArgMatches) -> Vec<Password> {
+    match util::candidate_passwords(matches) {
+        Ok(passwords) => passwords,
+        Err(err) => {
+            eprintln!("An error occured while reading the password. Error: {}", err);
+            exit(util::EXIT_INVALID_ARGUMENTS);
+        }
+    }
+}
+
+// Writes to the destination path if given, otherwise the archive is replaced.
+fn recrypt(matches: &ArgMatches, zip_file: &ZipFile, passwords: Vec<Password>, encryption: Option<ZipEncryption>, new_password: Option<Password>) {
+    let dest_path = match matches.value_of("dest_path").map(PathBuf::from).or_else(|| zip_file.zip_file_path().map(PathBuf::from)) {
+        Some(dest_path) => dest_path,
+        None => {
+            eprintln!("Invalid destination path. Exiting...");
+            exit(util::EXIT_INVALID_ARGUMENTS);
+        }
+    };
+
+    // Re-encrypting copies the content without decompressing it, so only the item paths are printed in verbose mode.
+    let progress = TerminalProgress::new(None, matches.is_present("verbose"));
+    let mut recrypt_options = RecryptOptions::new(&dest_path, passwords, Some(&util::TerminalPasswordPrompt), encryption, new_password);
+    recrypt_options.set_progress_observer(&progress);
+    recrypt_options.set_cancellation_token(util::cancel_on_interrupt());
+
+    if let Err(err) = zip_file.recrypt(&recrypt_options) {
+        eprintln!("An error occured while re-encrypting the ZIP file! Error: {}", err);
+        exit(util::exit_code(&err));
+    }
+}
@@ -0,0 +1,86 @@
+use std::process::exit;
+use std::path::{Path, PathBuf};
+use clap::ArgMatches;
+
+use ruzito::{ZipError, ZipErrorKind, ZipFile};
+
+use crate::{cli::CommandProcessor, util};
+
+pub struct TestCommand;
+
+impl CommandProcessor for TestCommand {
+    fn command_name(&self) -> &str {
+        "test"
+    }
+
+    fn process_command(&self, matches: &ArgMatches) {
+
+        let given_file_path = Path::new(matches.value_of(self.command_name()).unwrap());
+
+        let file_path = if given_file_path.is_absolute() {
+            let relative_path = match given_file_path.canonicalize() {
+                Ok(path_buf) => path_buf,
+                Err(err) => {
+                    eprintln!("An error occured while canonicalizing the given zip path. Error: {}", err);
+                    exit(util::EXIT_FAILURE);
+                }
+            };
+
+            if !relative_path.exists() {
+                eprintln!("Given file path does not exist!");
+                exit(util::EXIT_INVALID_ARGUMENTS);
+            }
+
+            relative_path
+        }
+        else {
+            PathBuf::new().join(given_file_path)
+        };
+
+        let mut zip_file = match ZipFile::new(file_path.clone()) {
+            Ok(zip_file) => zip_file,
+            Err(err) => {
+                eprintln!("An error occured while reading the ZIP file! Error: {}", err);
+                exit(util::exit_code(&err))
+            }
+        };
+
+        util::raise_decoder_limits(&mut zip_file, matches);
+
+        let passwords = match util::candidate_passwords(matches) {
+            Ok(passwords) => passwords,
+            Err(err) => {
+                eprintln!("An error occured while reading the password. Error: {}", err);
+                exit(util::EXIT_INVALID_ARGUMENTS);
+            }
+        };
+
+        let test_results = match zip_file.test_all(passwords, Some(&util::TerminalPasswordPrompt)) {
+            Ok(test_results) => test_results,
+            Err(err) => {
+                eprintln!("An error occured while testing the ZIP file! Error: {}", err);
+                exit(util::exit_code(&err));
+            }
+        };
+
+        for (item, result) in &test_results {
+            match result {
+                Ok(()) => println!("    testing: {}    OK", item.item_path()),
+                Err(err) if matches!(err.kind(), ZipErrorKind::UnsupportedCompression | ZipErrorKind::UnsupportedEncryption) => println!("    testing: {}    UNSUPPORTED ({})", item.item_path(), err),
+                Err(err) => println!("    testing: {}    FAILED ({})", item.item_path(), err)
+            }
+        }
+
+        // Exits with the code of the first item that failed.
+        let failed_items: Vec<&ZipError> = test_results.iter()
+            .filter_map(|(_, result)| result.as_ref().err())
+            .collect();
+        match failed_items.first() {
+            None => println!("No errors detected in {}.", file_path.display()),
+            Some(err) => {
+                println!("{} of {} items failed in {}.", failed_items.len(), test_results.len(), file_path.display());
+                exit(util::exit_code(err));
+            }
+        }
+    }
+}
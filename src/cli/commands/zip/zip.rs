@@ -1,7 +1,11 @@
 use std::{ffi::OsString, path::Path};
 use std::path::PathBuf;
-use std::fs::File;
-use crate::{util, cli::CommandProcessor, zip::{ZipFile, mem_map::EncryptionMethod, options::ZipOptions, zip_item_creator::ZipItemCreator}};
+use std::process::exit;
+use ruzito::{AesStrength, CompressionMethod, CompressionOptions, DEFAULT_COMPRESSION_LEVEL, DEFAULT_STORE_EXTENSIONS, DEFAULT_ZSTD_LEVEL, EncryptionMethod, EncryptionOptions, Pattern, ZipEncryption, ZipFile, ZipItemCreator, ZipOptions, ZstdOptions};
+
+use crate::{util, cli::{CommandProcessor, progress::TerminalProgress}};
+
+use super::COMPRESSION_LEVEL_ARGS;
 
 const MIN_ZIP_ITEM_CAPACITY: usize = 50;
 
@@ -28,13 +32,13 @@ impl CommandProcessor for ZipCommand {
                              Some(path) => path,
                              None => {
                                  eprintln!("An error occured while generating destination path. Exiting..");
-                                 return;
+                                 exit(util::EXIT_INVALID_ARGUMENTS);
                              }
                          }
                      },
                      None => {
                          eprintln!("Invalid zip path. Exiting...");
-                         return;
+                         exit(util::EXIT_INVALID_ARGUMENTS);
                      }
             }
         }
@@ -45,13 +49,13 @@ impl CommandProcessor for ZipCommand {
                 Ok(path_buf) => path_buf,
                 Err(err) => {
                     eprintln!("An error occured while canonicalizing the given zip path. Error: {}", err);
-                    return;
+                    exit(util::EXIT_FAILURE);
                 }
             };
 
             if !relative_path.exists() {
                 eprintln!("Given zip path does not exist!");
-                return;
+                exit(util::EXIT_INVALID_ARGUMENTS);
             }
             
             relative_path
@@ -59,18 +63,21 @@ impl CommandProcessor for ZipCommand {
         else { PathBuf::new().join(given_zip_path) };
 
         let dest_path = if given_dest_path.is_absolute() {
-            if !given_dest_path.exists() {
-                if let Err(err) = File::create(given_dest_path) {
-                    eprintln!("An error occured while creating destination zip file. Error: {}", err);
-                    return;
+            // The archive is only created once zipping succeeds, a missing destination is canonicalized through its folder.
+            let canonical_path = if given_dest_path.exists() {
+                given_dest_path.canonicalize()
+            } else {
+                match (given_dest_path.parent(), given_dest_path.file_name()) {
+                    (Some(parent), Some(file_name)) => parent.canonicalize().map(|parent| parent.join(file_name)),
+                    _ => Ok(given_dest_path.to_path_buf())
                 }
-            }
+            };
 
-            let relative_path = match given_dest_path.canonicalize() {
+            let relative_path = match canonical_path {
                 Ok(path_buf) => path_buf,
                 Err(err) => {
                     eprintln!("An error occured while canonicalizing the given destination path. Error: {}", err);
-                    return;
+                    exit(util::EXIT_FAILURE);
                 }
             };
 
@@ -85,51 +92,171 @@ impl CommandProcessor for ZipCommand {
             dest_path
         };
 
-        let encrypt_file = matches.is_present("encrypt") || matches.is_present("password");
+        let encrypt_file = matches.is_present("encrypt") || matches.is_present("password") || matches.is_present("password_env")
+            || matches.is_present("password_fd") || matches.is_present("password_file") || matches.is_present("encryption")
+            || matches.is_present("encrypt_only");
         let verbose_mode = matches.is_present("verbose");
 
-        let encryption_method = if encrypt_file { EncryptionMethod::ZipCrypto } else { EncryptionMethod::NoEncryption };
+        let threads = match matches.value_of("threads").map(|threads| threads.parse::<usize>()) {
+            Some(Ok(threads)) if threads > 0 => threads,
+            Some(_) => {
+                eprintln!("Number of threads must be a positive number. Exiting...");
+                exit(util::EXIT_INVALID_ARGUMENTS);
+            },
+            None => 1
+        };
+
+        let encryption = match matches.value_of("encryption") {
+            Some(encryption_name) => match encryption_from_name(encryption_name) {
+                Some(encryption) => Some(encryption),
+                None => {
+                    eprintln!("Unsupported encryption {}. Exiting...", encryption_name);
+                    exit(util::EXIT_INVALID_ARGUMENTS);
+                }
+            },
+            None if encrypt_file => Some(ZipEncryption::ZipCrypto),
+            None => None
+        };
+        let encryption_method = encryption.map_or(EncryptionMethod::NoEncryption, ZipEncryption::encryption_method);
+
+        let encryption_patterns = match matches.value_of("encrypt_only") {
+            Some(patterns) => {
+                let patterns = patterns.split(',')
+                    .map(|pattern| pattern.trim())
+                    .filter(|pattern| !pattern.is_empty())
+                    .map(Pattern::new)
+                    .collect::<Result<Vec<_>, _>>();
+
+                match patterns {
+                    Ok(patterns) => patterns,
+                    Err(err) => {
+                        eprintln!("Invalid pattern in --encrypt-only. Error: {}. Exiting...", err);
+                        exit(util::EXIT_INVALID_ARGUMENTS);
+                    }
+                }
+            },
+            None => Vec::new()
+        };
+        let encryption_options = encryption.map(|encryption| EncryptionOptions::new(encryption, encryption_patterns));
 
-        let zip_password = matches.value_of("password")
-            .map(|pass_str| String::from(pass_str));
+        // Zipping uses the first given password, e.g. the first line of --password-file.
+        let zip_password = match util::candidate_passwords(matches) {
+            Ok(passwords) => passwords.into_iter().next(),
+            Err(err) => {
+                eprintln!("An error occured while reading the password. Error: {}", err);
+                exit(util::EXIT_INVALID_ARGUMENTS);
+            }
+        };
 
         let zip_password = match zip_password {
             Some(pass) => Some(pass),
-            None => if encrypt_file {
-                match util::read_pass() {
-                    Ok(pass) => Some(pass),
-                    Err(_) => None
+            None if encrypt_file => match util::read_new_pass("Enter password: ") {
+                Ok(pass) => Some(pass),
+                Err(err) => {
+                    eprintln!("{} Exiting...", err);
+                    exit(util::EXIT_INVALID_ARGUMENTS);
                 }
-            } else {
-                None
-            }
+            },
+            None => None
+        };
+
+        let compression_level = (0..COMPRESSION_LEVEL_ARGS.len())
+            .find(|level| matches.is_present(COMPRESSION_LEVEL_ARGS[*level].0))
+            .map(|level| level as u32)
+            .unwrap_or(DEFAULT_COMPRESSION_LEVEL);
+
+        let compression_method = match matches.value_of("method") {
+            Some(method_name) => match compression_method_from_name(method_name) {
+                Some(compression_method) => Some(compression_method),
+                None => {
+                    eprintln!("Unsupported compression method {}. Exiting...", method_name);
+                    exit(util::EXIT_INVALID_ARGUMENTS);
+                }
+            },
+            None if compression_level == 0 => Some(CompressionMethod::NoCompression),
+            None => None
+        };
+
+        let store_extensions = match matches.value_of("store_extensions") {
+            Some(extensions) => extensions.split(',')
+                .map(|extension| extension.trim())
+                .filter(|extension| !extension.is_empty())
+                .map(String::from)
+                .collect(),
+            None => DEFAULT_STORE_EXTENSIONS.iter().map(|extension| extension.to_string()).collect()
+        };
+
+        let zstd_level = match matches.value_of("zstd_level").map(|level| level.parse::<i32>()) {
+            Some(Ok(level)) if (1..=22).contains(&level) => level,
+            Some(_) => {
+                eprintln!("Zstd level must be a number between 1 and 22. Exiting...");
+                exit(util::EXIT_INVALID_ARGUMENTS);
+            },
+            None => DEFAULT_ZSTD_LEVEL
         };
 
+        let zstd_long_window_log = util::zstd_long_window_log(matches);
+
         let mut zip_items = Vec::with_capacity(MIN_ZIP_ITEM_CAPACITY);
-        let zip_options = ZipOptions::new(&zip_path, &dest_path, encrypt_file, zip_password, verbose_mode);
+        let compression_options = CompressionOptions::new(compression_method, compression_level, store_extensions, ZstdOptions::new(zstd_level, zstd_long_window_log));
+        let mut zip_options = ZipOptions::new(&zip_path, &dest_path, encryption_options, zip_password, threads, compression_options);
         
-        let zip_item_creator = ZipItemCreator::new(&zip_path);
+        let zip_item_creator = ZipItemCreator::new(&zip_path, zip_options.compression_options());
 
         if verbose_mode {
             println!("Finding items to be zipped.");
         }
-        if let Err(err) = zip_item_creator.create_zip_items(&zip_path, None, &mut zip_items, encryption_method) {
-            eprintln!("An error occured while creating zip items! Err: {:?}", err);
-            return;
+        if let Err(err) = zip_item_creator.create_zip_items(&zip_path, None, &mut zip_items, zip_options.encryption_options()) {
+            eprintln!("An error occured while creating zip items! Error: {}", err);
+            exit(util::exit_code(&err));
         }
 
         if verbose_mode {
             println!("Finding items completed. {} items found.", zip_items.len());
         }
 
+        let total_bytes = zip_items.iter()
+            .map(|item| item.uncompressed_size() as u64)
+            .sum();
+        let progress = TerminalProgress::new(Some(total_bytes), verbose_mode);
+        zip_options.set_progress_observer(&progress);
+        zip_options.set_cancellation_token(util::cancel_on_interrupt());
+
         let mut zip_file = ZipFile::create(zip_items.len() as u16, zip_items, OsString::from(dest_path.as_os_str()), encryption_method);
 
-        match zip_file.create_zip_file(&zip_options) {
+        let zip_result = zip_file.create_zip_file(&zip_options);
+        progress.finish();
+
+        match zip_result {
             Ok(()) => {
                 //TODO: Successful message.
             },
-            Err(err) => eprintln!("An error occured while zipping the path Error: {:?}", err)
+            Err(err) => {
+                eprintln!("An error occured while zipping the path! Error: {}", err);
+                exit(util::exit_code(&err));
+            }
         };
 
     }
-}
\ No newline at end of file
+}
+
+fn compression_method_from_name(method_name: &str) -> Option<CompressionMethod> {
+    match method_name.to_lowercase().as_str() {
+        "store" => Some(CompressionMethod::NoCompression),
+        "deflate" => Some(CompressionMethod::Deflate),
+        "bzip2" => Some(CompressionMethod::BZIP2),
+        "zstd" => Some(CompressionMethod::ZStandard),
+        "lzma" => Some(CompressionMethod::LZMA),
+        "xz" => Some(CompressionMethod::XZ),
+        _ => None
+    }
+}
+
+pub fn encryption_from_name(encryption_name: &str) -> Option<ZipEncryption> {
+    match encryption_name.to_lowercase().as_str() {
+        "zipcrypto" => Some(ZipEncryption::ZipCrypto),
+        "aes128" => Some(ZipEncryption::WinZipAes(AesStrength::Aes128)),
+        "aes256" => Some(ZipEncryption::WinZipAes(AesStrength::Aes256)),
+        _ => None
+    }
+}
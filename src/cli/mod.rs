@@ -1,6 +1,7 @@
 use clap::{App, ArgMatches};
 
 mod commands;
+pub mod progress;
 use cli::commands::zip::ZipSubCommand;
 pub struct SubCommandModule {
     sub_commands: Vec<Box<dyn RuzitoSubCommand>>
@@ -0,0 +1,104 @@
+use std::{io::{IsTerminal, Write}, sync::{Mutex, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+
+use ruzito::ProgressObserver;
+
+const BAR_WIDTH: usize = 30;
+const RENDER_INTERVAL: Duration = Duration::from_millis(100);
+const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+// Renders a progress bar with the processed bytes, the throughput and the remaining time on stdout.
+// The bar is only shown when stdout is a terminal and the total size is known, in verbose mode the path of every item is printed as it starts.
+pub struct TerminalProgress {
+    total_bytes: Option<u64>,
+    processed_bytes: AtomicU64,
+    started_at: Instant,
+    // Time of the last rendering, the lock also keeps lines of different threads apart.
+    last_render: Mutex<Option<Instant>>,
+    verbose_mode: bool
+}
+
+impl TerminalProgress {
+    pub fn new(total_bytes: Option<u64>, verbose_mode: bool) -> Self {
+        Self {
+            total_bytes: total_bytes.filter(|_| std::io::stdout().is_terminal()),
+            processed_bytes: AtomicU64::new(0),
+            started_at: Instant::now(),
+            last_render: Mutex::new(None),
+            verbose_mode
+        }
+    }
+
+    // Renders the final state of the bar and moves to the next line.
+    pub fn finish(&self) {
+        if self.total_bytes.is_some() {
+            let mut last_render = self.last_render.lock().unwrap();
+            self.render(&mut last_render);
+            println!();
+        }
+    }
+
+    fn render(&self, last_render: &mut Option<Instant>) {
+        let total_bytes = match self.total_bytes {
+            Some(total_bytes) => total_bytes,
+            None => return
+        };
+        let processed_bytes = self.processed_bytes.load(Ordering::SeqCst).min(total_bytes);
+        let ratio = if total_bytes == 0 { 1.0 } else { processed_bytes as f64 / total_bytes as f64 };
+        let filled = (ratio * BAR_WIDTH as f64) as usize;
+
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        let throughput = if elapsed > 0.0 { processed_bytes as f64 / elapsed } else { 0.0 };
+        let eta = if throughput > 0.0 { format_duration((total_bytes - processed_bytes) as f64 / throughput) } else { String::from("--:--") };
+
+        print!("\r\x1b[2K[{}{}] {:3.0}% {} / {} {}/s ETA {}",
+            "=".repeat(filled), " ".repeat(BAR_WIDTH - filled), ratio * 100.0,
+            format_bytes(processed_bytes as f64), format_bytes(total_bytes as f64), format_bytes(throughput), eta);
+        let _ = std::io::stdout().flush();
+
+        *last_render = Some(Instant::now());
+    }
+}
+
+impl ProgressObserver for TerminalProgress {
+    fn entry_started(&self, item_path: &str, _size: u64) {
+        if self.verbose_mode {
+            let mut last_render = self.last_render.lock().unwrap();
+            if self.total_bytes.is_some() {
+                print!("\r\x1b[2K");
+            }
+            println!("{}", item_path);
+            self.render(&mut last_render);
+        }
+    }
+
+    fn bytes_processed(&self, bytes: u64) {
+        self.processed_bytes.fetch_add(bytes, Ordering::SeqCst);
+
+        if self.total_bytes.is_some() {
+            let mut last_render = self.last_render.lock().unwrap();
+            if last_render.is_none_or(|last_render| last_render.elapsed() >= RENDER_INTERVAL) {
+                self.render(&mut last_render);
+            }
+        }
+    }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 { format!("{:.0} {}", value, UNITS[unit]) } else { format!("{:.1} {}", value, UNITS[unit]) }
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
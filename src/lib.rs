@@ -0,0 +1,49 @@
+//! ruzito reads, extracts and creates ZIP files.
+//!
+//! The items of the crate root are the API of the library, the modules under them are not part of it.
+//! The `cli` feature (enabled by default) only adds the dependencies of the ruzito binary.
+
+extern crate byteorder;
+#[cfg(feature = "deflate")]
+extern crate inflate;
+#[cfg(feature = "deflate")]
+extern crate flate2;
+extern crate crc;
+extern crate chrono;
+extern crate rand;
+extern crate tempfile;
+extern crate aes;
+extern crate hmac;
+extern crate sha1;
+extern crate pbkdf2;
+extern crate glob;
+extern crate zeroize;
+#[cfg(feature = "bzip2")]
+extern crate bzip2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+#[cfg(feature = "lzma")]
+extern crate xz2;
+#[cfg(feature = "deflate")]
+extern crate deflate64;
+
+mod zip;
+
+pub use zip::{ItemTestResult, ZipError, ZipErrorKind, ZipFile, ZipFileIntoIterator};
+pub use zip::entry_reader::ZipEntryReader;
+pub use zip::writer::ZipWriter;
+pub use zip::progress::ProgressObserver;
+pub use zip::cancellation::CancellationToken;
+pub use zip::zip_metadata::CentralDirectoryIter;
+pub use zip::zip_item::ZipItem;
+pub use zip::zip_item_creator::ZipItemCreator;
+pub use zip::date_time::ZipDateTime;
+pub use zip::mem_map::{CompressionMethod, EncryptionMethod, ZipVersion};
+pub use zip::encryption::zip_crypto::{ZipCryptoReader, ZipCryptoWriter};
+pub use zip::encryption::winzip_aes::{AesExtraField, AesStrength, WinZipAesReader, WinZipAesWriter};
+
+// Compression methods, see `Codec` to add one.
+pub use zip::codec::{Codec, CodecRegistry, DecodeInfo};
+// Options of extracting, zipping and re-encrypting ZIP files.
+pub use zip::options::{CompressionOptions, EncryptionOptions, EntryOptions, ExtractOptions, Password, PasswordPrompt, Pattern, RecryptOptions, ZipEncryption, ZipOptions, ZstdOptions};
+pub use zip::options::{DEFAULT_COMPRESSION_LEVEL, DEFAULT_STORE_EXTENSIONS, DEFAULT_ZSTD_LEVEL};
@@ -1,18 +1,18 @@
+extern crate ruzito;
 extern crate clap;
-extern crate byteorder;
-extern crate inflate;
-extern crate flate2;
-extern crate crc;
 extern crate cli_table;
 extern crate rpassword;
-extern crate chrono;
-extern crate rand;
+extern crate zeroize;
+extern crate ctrlc;
+#[cfg(unix)]
+extern crate libc;
 
-mod zip;
 mod cli;
 mod util;
 
-use clap::{App, AppSettings};
+use std::process::exit;
+
+use clap::{App, AppSettings, ErrorKind};
 use cli::SubCommandModule;
 
 fn main() {
@@ -25,7 +25,14 @@ fn main() {
         .about("Simple Archive Extraction Tool")
         .settings(&[AppSettings::ArgRequiredElseHelp, AppSettings::ArgsNegateSubcommands, AppSettings::SubcommandRequiredElseHelp])
         .subcommands(commands.sub_commands())
-        .get_matches();
+        .get_matches_safe()
+        .unwrap_or_else(|err| match err.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => err.exit(),
+            _ => {
+                eprintln!("{}", err.message);
+                exit(util::EXIT_INVALID_ARGUMENTS);
+            }
+        });
 
     commands.run(matches);
 }
\ No newline at end of file
@@ -1,14 +1,292 @@
-use std::io::{Error, Write};
+use std::{fs::File, io::{Error, ErrorKind, Read, Write}, path::Path, process::exit};
 
-pub fn read_pass() -> Result<String, Error> {
-    print!("Enter password: ");
-    if let Err(err) = std::io::stdout().flush() {
-        return Err(err)
+use clap::ArgMatches;
+use zeroize::Zeroizing;
+
+use ruzito::{CancellationToken, Password, PasswordPrompt, ZipError, ZipErrorKind, ZipFile};
+
+// Asks the password twice, used when encrypting so that a typo does not lock the files.
+pub fn read_new_pass(prompt: &str) -> Result<Password, Error> {
+    confirm_pass(prompt, prompt_pass)
+}
+
+fn confirm_pass<F>(prompt: &str, mut prompt_pass: F) -> Result<Password, Error>
+where F: FnMut(&str) -> Result<Password, Error> {
+    let pass = prompt_pass(prompt)?;
+    let confirmation = prompt_pass("Confirm password: ")?;
+
+    if pass != confirmation {
+        return Err(Error::new(ErrorKind::InvalidInput, "Passwords do not match."));
     }
-    let pass = match rpassword::read_password() {
-        Ok(pass) => pass,
-        Err(err) => return Err(err)
-    };
 
     Ok(pass)
+}
+
+fn prompt_pass(prompt: &str) -> Result<Password, Error> {
+    print!("{}", prompt);
+    std::io::stdout().flush()?;
+    let pass = rpassword::read_password()?;
+
+    Ok(Password::new(pass))
+}
+
+// Collects the passwords given with --password, --password-env, --password-fd and --password-file, in this order.
+pub fn candidate_passwords(matches: &ArgMatches) -> Result<Vec<Password>, Error> {
+    let mut passwords: Vec<Password> = matches.value_of("password")
+        .map(|password| Password::new(String::from(password)))
+        .into_iter()
+        .collect();
+
+    if let Some(var) = matches.value_of("password_env") {
+        let password = std::env::var(var)
+            .map_err(|_| Error::new(ErrorKind::NotFound, format!("The environment variable {} is not set.", var)))?;
+        passwords.push(Password::new(password));
+    }
+
+    if let Some(fd) = matches.value_of("password_fd") {
+        let fd = fd.parse::<i32>()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("Invalid file descriptor {}.", fd)))?;
+        passwords.extend(read_password_fd(fd)?);
+    }
+
+    if let Some(password_file) = matches.value_of("password_file") {
+        passwords.extend(read_password_file(Path::new(password_file))?);
+    }
+
+    Ok(passwords)
+}
+
+// The new password of --rekey, given with --new-password or --new-password-env, or asked on the terminal.
+pub fn new_password(matches: &ArgMatches) -> Result<Password, Error> {
+    if let Some(password) = matches.value_of("new_password") {
+        return Ok(Password::new(String::from(password)));
+    }
+
+    if let Some(var) = matches.value_of("new_password_env") {
+        return std::env::var(var)
+            .map(Password::new)
+            .map_err(|_| Error::new(ErrorKind::NotFound, format!("The environment variable {} is not set.", var)));
+    }
+
+    read_new_pass("Enter new password: ")
+}
+
+// Reads the passwords of a file, one password per line.
+fn read_password_file(path: &Path) -> Result<Vec<Password>, Error> {
+    read_passwords(File::open(path)?)
+}
+
+#[cfg(unix)]
+fn read_password_fd(fd: i32) -> Result<Vec<Password>, Error> {
+    use std::os::unix::io::FromRawFd;
+
+    // Standard output and error stay open for the messages of ruzito.
+    if fd == 1 || fd == 2 {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("The file descriptor {} is not readable.", fd)));
+    }
+
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("The file descriptor {} is not open.", fd)));
+    }
+    if flags & libc::O_ACCMODE == libc::O_WRONLY {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("The file descriptor {} is not readable.", fd)));
+    }
+
+    // The descriptor is handed over by the parent process for ruzito to read and close, like gpg's --passphrase-fd.
+    let file = unsafe { File::from_raw_fd(fd) };
+    read_passwords(file)
+}
+
+#[cfg(not(unix))]
+fn read_password_fd(_fd: i32) -> Result<Vec<Password>, Error> {
+    Err(Error::new(ErrorKind::Unsupported, "--password-fd is only supported on Unix."))
+}
+
+fn read_passwords<R: Read>(mut reader: R) -> Result<Vec<Password>, Error> {
+    let mut content = Zeroizing::new(String::new());
+    reader.read_to_string(&mut content)?;
+
+    Ok(content.lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(|line| Password::new(String::from(line)))
+        .collect())
+}
+
+// Asks the password of an encrypted item on the terminal, an empty password skips the item.
+pub struct TerminalPasswordPrompt;
+
+impl PasswordPrompt for TerminalPasswordPrompt {
+    fn ask_password(&self, item_path: &str, retry: bool) -> Option<Password> {
+        if retry {
+            println!("Incorrect password.");
+        }
+
+        prompt_pass(&format!("Password for {} (leave empty to skip): ", item_path)).ok()
+            .filter(|password| !password.as_str().is_empty())
+    }
+}
+
+// The --zstd-long window given as a power of two.
+pub fn zstd_long_window_log(matches: &ArgMatches) -> Option<u32> {
+    match matches.value_of("zstd_long").map(|window_log| window_log.parse::<u32>()) {
+        Some(Ok(window_log)) if (10..=31).contains(&window_log) => Some(window_log),
+        Some(_) => {
+            eprintln!("Zstd long distance window must be a number between 10 and 31. Exiting...");
+            exit(EXIT_INVALID_ARGUMENTS);
+        },
+        None => None
+    }
+}
+
+// The --lzma-memory-limit given in MiB.
+#[cfg(feature = "lzma")]
+fn lzma_memory_limit(matches: &ArgMatches) -> Option<u64> {
+    let memory_limit = matches.value_of("lzma_memory_limit")
+        .map(|memory_limit| memory_limit.parse::<u64>().ok().filter(|memory_limit| *memory_limit > 0).and_then(|memory_limit| memory_limit.checked_mul(1024 * 1024)));
+
+    match memory_limit {
+        Some(Some(memory_limit)) => Some(memory_limit),
+        Some(None) => {
+            eprintln!("LZMA memory limit must be a positive number of MiB. Exiting...");
+            exit(EXIT_INVALID_ARGUMENTS);
+        },
+        None => None
+    }
+}
+
+// Extracting and testing only accept zstd windows above 2^27 bytes when --zstd-long allows them,
+// and LZMA and XZ items needing more than 128 MiB when --lzma-memory-limit allows them.
+#[cfg_attr(not(all(feature = "zstd", feature = "lzma")), allow(unused_variables, unused_mut))]
+pub fn raise_decoder_limits(zip_file: &mut ZipFile, matches: &ArgMatches) {
+    let mut codec_registry = ruzito::CodecRegistry::default();
+    #[cfg(feature = "zstd")]
+    if let Some(window_log) = zstd_long_window_log(matches) {
+        codec_registry.set_zstd_window_log_max(window_log);
+    }
+    #[cfg(feature = "lzma")]
+    if let Some(memory_limit) = lzma_memory_limit(matches) {
+        codec_registry.set_lzma_memory_limit(memory_limit);
+    }
+    zip_file.set_codec_registry(codec_registry);
+}
+
+// Exit codes of ruzito, documented in the README.
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_INVALID_ARGUMENTS: i32 = 2;
+pub const EXIT_INVALID_ARCHIVE: i32 = 3;
+pub const EXIT_CHECKSUM_MISMATCH: i32 = 4;
+pub const EXIT_PASSWORD: i32 = 5;
+pub const EXIT_UNSUPPORTED: i32 = 6;
+pub const EXIT_INVALID_PATH: i32 = 7;
+pub const EXIT_ITEM_NOT_FOUND: i32 = 8;
+// 128 + SIGINT, like shells report a process stopped with Ctrl+C.
+pub const EXIT_CANCELLED: i32 = 130;
+
+pub fn exit_code(err: &ZipError) -> i32 {
+    match err.kind() {
+        ZipErrorKind::Io | ZipErrorKind::ReaderInUse => EXIT_FAILURE,
+        ZipErrorKind::InvalidArchive => EXIT_INVALID_ARCHIVE,
+        ZipErrorKind::ChecksumMismatch => EXIT_CHECKSUM_MISMATCH,
+        ZipErrorKind::IncorrectPassword | ZipErrorKind::PasswordRequired => EXIT_PASSWORD,
+        ZipErrorKind::UnsupportedCompression | ZipErrorKind::UnsupportedEncryption | ZipErrorKind::Zip64Required => EXIT_UNSUPPORTED,
+        ZipErrorKind::UnsafePath | ZipErrorKind::InvalidPath => EXIT_INVALID_PATH,
+        ZipErrorKind::ItemNotFound => EXIT_ITEM_NOT_FOUND,
+        ZipErrorKind::Cancelled => EXIT_CANCELLED
+    }
+}
+
+// Cancels the returned token on Ctrl+C so that the running job can clean up, a second Ctrl+C exits right away.
+pub fn cancel_on_interrupt() -> CancellationToken {
+    let cancellation_token = CancellationToken::new();
+    let handler_token = cancellation_token.clone();
+
+    let handler_result = ctrlc::set_handler(move || {
+        if handler_token.is_cancelled() {
+            std::process::exit(EXIT_CANCELLED);
+        }
+        handler_token.cancel();
+    });
+    if let Err(err) = handler_result {
+        eprintln!("Ctrl+C cannot be handled, interrupting may leave partial files behind. Error: {}", err);
+    }
+
+    cancellation_token
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cli::SubCommandModule;
+
+    fn zip_matches(args: &[&str]) -> ArgMatches<'static> {
+        SubCommandModule::new().sub_commands().remove(0)
+            .get_matches_from(std::iter::once("zip").chain(args.iter().cloned()))
+    }
+
+    fn as_strs(passwords: &[Password]) -> Vec<&str> {
+        passwords.iter().map(Password::as_str).collect()
+    }
+
+    #[test]
+    fn reads_one_password_per_line() {
+        let passwords = read_passwords("first\r\n\nsecond\n\r\n third \n".as_bytes()).unwrap();
+        assert_eq!(as_strs(&passwords), vec!["first", "second", " third "]);
+    }
+
+    #[test]
+    fn collects_candidate_passwords_in_order() {
+        let password_file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(password_file.path(), "from file\r\nsecond from file\r\n").unwrap();
+        std::env::set_var("RUZITO_TEST_CANDIDATE_PASSWORD", "from env");
+
+        let matches = zip_matches(&["--password", "from args", "--password-env", "RUZITO_TEST_CANDIDATE_PASSWORD", "--password-file", password_file.path().to_str().unwrap()]);
+        let passwords = candidate_passwords(&matches).unwrap();
+
+        assert_eq!(as_strs(&passwords), vec!["from args", "from env", "from file", "second from file"]);
+    }
+
+    #[test]
+    fn fails_on_unset_password_env() {
+        let matches = zip_matches(&["--password-env", "RUZITO_TEST_UNSET_PASSWORD"]);
+        assert_eq!(candidate_passwords(&matches).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_passwords_of_file_descriptor() {
+        use std::os::unix::io::IntoRawFd;
+
+        let password_file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(password_file.path(), "from fd\n").unwrap();
+        let fd = File::open(password_file.path()).unwrap().into_raw_fd();
+
+        let matches = zip_matches(&["--password-fd", &fd.to_string()]);
+        assert_eq!(as_strs(&candidate_passwords(&matches).unwrap()), vec!["from fd"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_unusable_file_descriptors() {
+        use std::os::unix::io::IntoRawFd;
+
+        let password_file = tempfile::NamedTempFile::new().unwrap();
+        let write_only_fd = std::fs::OpenOptions::new().write(true).open(password_file.path()).unwrap().into_raw_fd();
+
+        for fd in &[1, 2, -1, write_only_fd] {
+            assert_eq!(read_password_fd(*fd).unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+        unsafe { libc::close(write_only_fd) };
+    }
+
+    #[test]
+    fn asks_new_password_twice() {
+        let answers = |answers: Vec<&'static str>| {
+            let mut answers = answers.into_iter();
+            move |_: &str| Ok(Password::new(String::from(answers.next().unwrap())))
+        };
+
+        assert_eq!(confirm_pass("Password: ", answers(vec!["secret", "secret"])).unwrap().as_str(), "secret");
+        assert_eq!(confirm_pass("Password: ", answers(vec!["secret", "typo"])).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
 }
\ No newline at end of file
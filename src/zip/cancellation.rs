@@ -0,0 +1,140 @@
+use std::{io::{Read, Write}, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+
+use zip::{ZipError, ZipErrorKind};
+
+// Cancels a running extraction or zipping from another thread, e.g. a signal handler.
+// Clones share their state, the job stops with a Cancelled error soon after `cancel` is called.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), ZipError> {
+        if self.is_cancelled() {
+            return Err(ZipError::new(ZipErrorKind::Cancelled));
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn check_cancelled(cancellation_token: Option<&CancellationToken>) -> Result<(), ZipError> {
+    cancellation_token.map_or(Ok(()), CancellationToken::check)
+}
+
+// Fails every read once the token is cancelled, so that the content of a large item is not read to the end.
+pub(crate) struct CancellableReader<'a, R> {
+    reader: R,
+    cancellation_token: Option<&'a CancellationToken>
+}
+
+impl<'a, R> CancellableReader<'a, R> {
+    pub fn new(reader: R, cancellation_token: Option<&'a CancellationToken>) -> Self {
+        Self {
+            reader,
+            cancellation_token
+        }
+    }
+}
+
+impl<'a, R: Read> Read for CancellableReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        check_cancelled(self.cancellation_token)?;
+        self.reader.read(buf)
+    }
+}
+
+// Fails every write once the token is cancelled.
+pub(crate) struct CancellableWriter<'a, W> {
+    writer: W,
+    cancellation_token: Option<&'a CancellationToken>
+}
+
+impl<'a, W> CancellableWriter<'a, W> {
+    pub fn new(writer: W, cancellation_token: Option<&'a CancellationToken>) -> Self {
+        Self {
+            writer,
+            cancellation_token
+        }
+    }
+}
+
+impl<'a, W: Write> Write for CancellableWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        check_cancelled(self.cancellation_token)?;
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ffi::OsString, io::Cursor};
+    use zip::{ProgressObserver, ZipFile, mem_map::EncryptionMethod, options::{CompressionOptions, EntryOptions, ExtractOptions, ZipOptions}, writer::ZipWriter, zip_item_creator::ZipItemCreator};
+
+    // Cancels the job as soon as the first bytes are written, like a Ctrl+C in the middle of an item.
+    struct CancellingObserver(CancellationToken);
+
+    impl ProgressObserver for CancellingObserver {
+        fn bytes_processed(&self, _bytes: u64) {
+            self.0.cancel();
+        }
+    }
+
+    #[test]
+    fn removes_partially_extracted_item() {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.add_bytes("large.txt", &b"Large content ".repeat(10000), &EntryOptions::default()).unwrap();
+        let mut zip_file = ZipFile::from_reader(Cursor::new(zip_writer.finish().unwrap().into_inner())).unwrap();
+
+        let cancellation_token = CancellationToken::new();
+        let observer = CancellingObserver(cancellation_token.clone());
+        let dest_dir = tempfile::tempdir().unwrap();
+        let mut options = ExtractOptions::new(dest_dir.path(), Vec::new(), None, 1);
+        options.set_progress_observer(&observer);
+        options.set_cancellation_token(cancellation_token);
+
+        let err = zip_file.extract_all(options).unwrap_err();
+        assert_eq!(err.kind(), ZipErrorKind::Cancelled);
+        assert!(!dest_dir.path().join("large.txt").exists());
+    }
+
+    #[test]
+    fn keeps_destination_when_zipping_is_cancelled() {
+        let source_dir = tempfile::tempdir().unwrap();
+        std::fs::write(source_dir.path().join("notes.txt"), b"Notes").unwrap();
+        let base_path = source_dir.path().to_path_buf();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let dest_path = dest_dir.path().join("notes.zip");
+        std::fs::write(&dest_path, b"previous archive").unwrap();
+
+        let mut zip_options = ZipOptions::new(&base_path, &dest_path, None, None, 1, CompressionOptions::default());
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        zip_options.set_cancellation_token(cancellation_token);
+
+        let mut zip_items = Vec::new();
+        ZipItemCreator::new(&base_path, zip_options.compression_options()).create_zip_items(&base_path, None, &mut zip_items, None).unwrap();
+        let mut zip_file = ZipFile::create(zip_items.len() as u16, zip_items, OsString::from(dest_path.as_os_str()), EncryptionMethod::NoEncryption);
+
+        let err = zip_file.create_zip_file(&zip_options).unwrap_err();
+        assert_eq!(err.kind(), ZipErrorKind::Cancelled);
+        assert_eq!(std::fs::read(&dest_path).unwrap(), b"previous archive");
+        assert_eq!(std::fs::read_dir(dest_dir.path()).unwrap().count(), 1);
+    }
+}
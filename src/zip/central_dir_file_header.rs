@@ -1,11 +1,17 @@
 use std::io::{ErrorKind, prelude::*};
-use std::io::SeekFrom;
 use std::io::Error;
-use super::{mem_map::{HostOS, CENTRAL_DIR_SIGNATURE, MINIMUM_SIZE_TO_COMPRESS, ZipVersion, CompressionMethod, EncryptionMethod}};
+use super::{encryption::winzip_aes::AesExtraField, mem_map::{HostOS, CENTRAL_DIR_SIGNATURE, ZIP_SPECIFICATION_VERSION, ZipVersion, CompressionMethod, EncryptionMethod}};
 use byteorder::{LittleEndian, ByteOrder};
 use super::date_time::*;
 use super::zip_item::ZipItem;
 
+const UNIX_FILE_TYPE: u32 = 0o100000;
+const UNIX_DIRECTORY_TYPE: u32 = 0o040000;
+const UNIX_PERMISSIONS_MASK: u32 = 0o7777;
+const MS_DOS_DIRECTORY_ATTR: u32 = 0x10;
+pub const MIN_CENTRAL_DIRECTORY_FILE_HEADER_SIZE: usize = 46;
+
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct CentralDirectoryFileHeader {
     signature: u32,
@@ -27,6 +33,7 @@ pub struct CentralDirectoryFileHeader {
     external_file_attr: u32,
     relative_offset: u32,
     file_name: String,
+    extra_field: Vec<u8>,
     file_comment: String
 }
 
@@ -35,7 +42,7 @@ impl CentralDirectoryFileHeader {
     pub fn from_reader<R>(reader: &mut R) -> Result<Self, Error>
     where R: Read + Seek {
         
-        let mut cdf_bytes = vec![0; 46];
+        let mut cdf_bytes = vec![0; MIN_CENTRAL_DIRECTORY_FILE_HEADER_SIZE];
         reader.read_exact(&mut cdf_bytes)?;
 
         let reader_signature = LittleEndian::read_u32(&cdf_bytes[0..4]);
@@ -47,6 +54,7 @@ impl CentralDirectoryFileHeader {
         let mut file_name_bytes: Vec<u8> = vec![0; file_name_length as usize];
 
         let extra_field_length = LittleEndian::read_u16(&cdf_bytes[30..32]);
+        let mut extra_field: Vec<u8> = vec![0; extra_field_length as usize];
 
         let file_comment_length = LittleEndian::read_u16(&cdf_bytes[32..34]);
         let mut file_comment_bytes: Vec<u8> = vec![0; file_comment_length as usize];
@@ -54,16 +62,11 @@ impl CentralDirectoryFileHeader {
         let general_purpose_flag = LittleEndian::read_u16(&cdf_bytes[8..10]);
 
         reader.read_exact(&mut file_name_bytes)?;
-        reader.seek(SeekFrom::Current(extra_field_length as i64))?;
+        reader.read_exact(&mut extra_field)?;
         reader.read_exact(&mut file_comment_bytes)?;
 
-        let encryption_method = if general_purpose_flag & 0b100001 == 0b100001 {
-            EncryptionMethod::WinZipAesEncryption
-        }
-        else if general_purpose_flag & 0x1 == 1 {
-            EncryptionMethod::ZipCrypto
-        }
-        else { EncryptionMethod::NoEncryption };
+        let compression_method = CompressionMethod::from_addr(LittleEndian::read_u16(&cdf_bytes[10..12]));
+        let encryption_method = EncryptionMethod::from_header(general_purpose_flag, compression_method);
 
         let cdfh = CentralDirectoryFileHeader {
             signature: CENTRAL_DIR_SIGNATURE,
@@ -72,7 +75,7 @@ impl CentralDirectoryFileHeader {
             version_needed_to_extract:  ZipVersion::from_byte(cdf_bytes[6]),
             general_purpose_flag,
             encryption_method,
-            compression_method: CompressionMethod::from_addr(LittleEndian::read_u16(&cdf_bytes[10..12])),
+            compression_method,
             last_modified_date_time: ZipDateTime::from_addr(LittleEndian::read_u16(&cdf_bytes[14..16]), LittleEndian::read_u16(&cdf_bytes[12..14])),
             crc32: LittleEndian::read_u32(&cdf_bytes[16..20]),
             compressed_size: LittleEndian::read_u32(&cdf_bytes[20..24]),
@@ -84,8 +87,10 @@ impl CentralDirectoryFileHeader {
             internal_file_attr: LittleEndian::read_u16(&cdf_bytes[36..38]),
             external_file_attr: LittleEndian::read_u32(&cdf_bytes[38..42]),
             relative_offset: LittleEndian::read_u32(&cdf_bytes[42..46]),
-            file_name: String::from_utf8(file_name_bytes).unwrap(),
-            file_comment: String::from_utf8(file_comment_bytes).unwrap()
+            file_name: String::from_utf8(file_name_bytes)
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "The file name is not valid UTF-8."))?,
+            extra_field,
+            file_comment: String::from_utf8_lossy(&file_comment_bytes).into_owned()
         };
 
         Ok(cdfh)
@@ -93,46 +98,48 @@ impl CentralDirectoryFileHeader {
 
     pub fn from_zip_item(zip_item: &ZipItem) -> Self {
 
+        let extra_field = zip_item.extra_field();
+
+        // Permissions are kept as a Unix mode in the upper half of the external attributes, like Info-ZIP does.
+        let (host_os, external_file_attr) = match zip_item.unix_permissions() {
+            Some(unix_permissions) if zip_item.is_file() => (HostOS::Unix, (UNIX_FILE_TYPE | unix_permissions & UNIX_PERMISSIONS_MASK) << 16),
+            Some(unix_permissions) => (HostOS::Unix, (UNIX_DIRECTORY_TYPE | unix_permissions & UNIX_PERMISSIONS_MASK) << 16 | MS_DOS_DIRECTORY_ATTR),
+            None => (HostOS::MsDos, 0)
+        };
+
         CentralDirectoryFileHeader {
             signature: CENTRAL_DIR_SIGNATURE,
-            host_os: HostOS::from_os(),
-            zip_specification: ZipVersion::new(2, 0),
-            version_needed_to_extract: ZipVersion::new(2,0 ),
-            general_purpose_flag: if zip_item.encryption_method() == EncryptionMethod::ZipCrypto { 0x01 } else { 0x00 },
+            host_os,
+            zip_specification: ZIP_SPECIFICATION_VERSION,
+            version_needed_to_extract: zip_item.version_needed_to_extract(),
+            general_purpose_flag: zip_item.general_purpose_flag(),
             encryption_method: zip_item.encryption_method(),
-            compression_method: zip_item.compression_method(),
+            compression_method: zip_item.header_compression_method(),
             last_modified_date_time: zip_item.modified_date_time().to_owned(),
-            crc32: zip_item.crc32(),
+            crc32: zip_item.header_crc32(),
             compressed_size: zip_item.compressed_size(),
             uncompressed_size: zip_item.uncompressed_size(),
             file_name_length: zip_item.item_path().len() as u16,
-            extra_field_length: 0,
-            file_comment_length: 0,
+            extra_field_length: extra_field.len() as u16,
+            file_comment_length: zip_item.comment().len() as u16,
             disk_number_start: 0,
             internal_file_attr: 0,
-            external_file_attr: 0,
+            external_file_attr,
             relative_offset: zip_item.start_offset(),
             file_name: zip_item.item_path().to_owned(),
-            file_comment: String::from("")
+            extra_field,
+            file_comment: zip_item.comment().to_owned()
         }
 
     }
 
-    pub fn to_binary(self) -> Vec<u8> {
+    pub fn to_binary(&self) -> Vec<u8> {
         let mut cdfh_bin: Vec<u8> = Vec::with_capacity(46);
         let mut signature = vec![0x50u8, 0x4B, 0x01, 0x02]; 
-        let mut version_needed_to_extract = vec![0x14u8, 0x00];
-        let mut min_version_to_extract = vec![0x14u8, 0x00];
-        let mut general_purpose_bit_flag = if self.encryption_method != EncryptionMethod::NoEncryption {
-            vec![0x01u8, 0x00]
-        } else {
-            vec![0x00, 0x00]
-        };
-        let mut compression_method = if self.uncompressed_size < MINIMUM_SIZE_TO_COMPRESS {
-            vec![0x00, 0x00]
-        } else {
-            vec![0x08u8, 0x00]
-        };
+        let mut version_made_by = vec![self.zip_specification.to_byte(), self.host_os.to_byte()];
+        let mut min_version_to_extract = vec![self.version_needed_to_extract.to_byte(), 0x00];
+        let mut general_purpose_bit_flag = vec![0, 0];
+        let mut compression_method = vec![0, 0];
         let mut last_modification_time_bytes = vec![0, 0];
         let mut last_modification_day_bytes = vec![0, 0];
         let mut last_modification_time = 0;
@@ -153,6 +160,8 @@ impl CentralDirectoryFileHeader {
 
         LittleEndian::write_u16(&mut last_modification_day_bytes, last_modification_day);
         LittleEndian::write_u16(&mut last_modification_time_bytes, last_modification_time);
+        LittleEndian::write_u16(&mut general_purpose_bit_flag, self.general_purpose_flag);
+        LittleEndian::write_u16(&mut compression_method, self.compression_method.to_addr());
         LittleEndian::write_u32(&mut compressed_size, self.compressed_size);
         LittleEndian::write_u32(&mut uncompressed_size, self.uncompressed_size);
         LittleEndian::write_u32(&mut crc32, self.crc32);
@@ -160,9 +169,11 @@ impl CentralDirectoryFileHeader {
         LittleEndian::write_u16(&mut file_name_length, self.file_name_length);
         LittleEndian::write_u16(&mut extra_field_length, self.extra_field_length);
         LittleEndian::write_u16(&mut disk_number_start, self.disk_number_start);
+        LittleEndian::write_u16(&mut file_comment_length, self.file_comment_length);
+        LittleEndian::write_u32(&mut external_file_attributes, self.external_file_attr);
 
         cdfh_bin.append(&mut signature);
-        cdfh_bin.append(&mut version_needed_to_extract);
+        cdfh_bin.append(&mut version_made_by);
         cdfh_bin.append(&mut min_version_to_extract);
         cdfh_bin.append(&mut general_purpose_bit_flag);
         cdfh_bin.append(&mut compression_method);
@@ -179,24 +190,43 @@ impl CentralDirectoryFileHeader {
         cdfh_bin.append(&mut external_file_attributes);
         cdfh_bin.append(&mut relative_offset);
         cdfh_bin.append(&mut file_name);
+        cdfh_bin.extend_from_slice(&self.extra_field);
+        cdfh_bin.extend_from_slice(self.file_comment.as_bytes());
 
         cdfh_bin
     }
 
 }
 
-impl Into<ZipItem> for CentralDirectoryFileHeader {
-    fn into(self) -> ZipItem {
-        ZipItem::new(
-            self.compression_method,
-            self.file_name,
-            self.uncompressed_size,
-            self.compressed_size,
-            self.last_modified_date_time,
-            self.relative_offset,
-            self.encryption_method,
-            self.crc32
-        )
+impl From<CentralDirectoryFileHeader> for ZipItem {
+    fn from(val: CentralDirectoryFileHeader) -> Self {
+        // The compression method of WinZip AES encrypted items is in their extra field.
+        let aes_extra_field = Some(val.compression_method)
+            .filter(|compression_method| *compression_method == CompressionMethod::Aex)
+            .and_then(|_| AesExtraField::from_extra_field(&val.extra_field));
+        let compression_method = aes_extra_field
+            .map_or(val.compression_method, |aes_extra_field| aes_extra_field.compression_method());
+
+        let unix_permissions = Some(val.external_file_attr >> 16)
+            .filter(|mode| val.host_os == HostOS::Unix && *mode != 0)
+            .map(|mode| mode & UNIX_PERMISSIONS_MASK);
+
+        let mut zip_item = ZipItem::new(
+            compression_method,
+            val.file_name,
+            val.uncompressed_size,
+            val.compressed_size,
+            val.last_modified_date_time,
+            val.relative_offset,
+            val.encryption_method,
+            val.crc32
+        );
+        zip_item.update_aes_extra_field(aes_extra_field);
+        zip_item.update_unix_permissions(unix_permissions);
+        zip_item.update_comment(val.file_comment);
+        zip_item.update_compression_flags(Some(val.general_purpose_flag));
+
+        zip_item
     }
 }
 
@@ -251,4 +281,13 @@ mod tests {
         assert_eq!(central_dir_file.file_comment, String::from(""));
     }
 
+    #[test]
+    fn version_made_by_is_kept_apart_from_version_needed() {
+        let zip_item = ZipItem::new(CompressionMethod::BZIP2, String::from("notes.txt"), 0, 0, ZipDateTime::new(28, 2, 2020, 12, 32, 32), 0, EncryptionMethod::NoEncryption, 0);
+        let cdfh_bin = CentralDirectoryFileHeader::from_zip_item(&zip_item).to_binary();
+
+        assert_eq!(cdfh_bin[4], ZIP_SPECIFICATION_VERSION.to_byte());
+        assert_eq!(cdfh_bin[6], 46);
+    }
+
 }
\ No newline at end of file
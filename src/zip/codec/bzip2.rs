@@ -0,0 +1,52 @@
+use std::io::{BufRead, Read, Write};
+
+use bzip2::{Compression, bufread::BzEncoder, read::BzDecoder};
+
+use zip::{mem_map::CompressionMethod, options::CompressionOptions};
+
+use super::{Codec, DecodeInfo};
+
+pub struct Bzip2Codec;
+
+impl Codec for Bzip2Codec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::BZIP2
+    }
+
+    fn decode(&self, reader: &mut dyn Read, writer: &mut dyn Write, _decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        let mut bzip2_decoder = BzDecoder::new(reader);
+        std::io::copy(&mut bzip2_decoder, writer)
+    }
+
+    fn decoder<'r>(&self, reader: Box<dyn Read + 'r>, _decode_info: &DecodeInfo) -> std::io::Result<Box<dyn Read + 'r>> {
+        Ok(Box::new(BzDecoder::new(reader)))
+    }
+
+    fn encode(&self, reader: &mut dyn BufRead, writer: &mut dyn Write, compression_options: &CompressionOptions) -> std::io::Result<u64> {
+        // bzip2 block sizes go from 1 to 9, there is no level 0.
+        let mut bzip2_encoder = BzEncoder::new(reader, Compression::new(compression_options.compression_level().clamp(1, 9)));
+        std::io::copy(&mut bzip2_encoder, writer)
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn bzip2_encoded_data_decodes_back() {
+        let data: Vec<u8> = (0..100_000).map(|index| ((index / 7) % 251) as u8).collect();
+        let mut encoded = Vec::new();
+        let mut decoded = Vec::new();
+
+        Bzip2Codec.encode(&mut Cursor::new(&data), &mut encoded, &CompressionOptions::default()).unwrap();
+        Bzip2Codec.decode(&mut Cursor::new(&encoded), &mut decoded, &DecodeInfo::new(0, data.len() as u64)).unwrap();
+
+        assert_eq!(decoded, data);
+    }
+}
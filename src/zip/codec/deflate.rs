@@ -0,0 +1,259 @@
+use std::{collections::BTreeMap, io::{BufRead, Error, Read, Write}, sync::{Mutex, mpsc}, thread};
+
+use crc::crc32;
+use deflate64::Deflate64Decoder;
+use flate2::{Compress, Compression, FlushCompress, Status, bufread::DeflateEncoder};
+use inflate::DeflateDecoder;
+
+use zip::{crc32::combine_checksums, mem_map::CompressionMethod, options::CompressionOptions};
+
+use super::{Codec, DecodeInfo};
+
+pub const MIN_SIZE_TO_DEFLATE_IN_PARALLEL: u64 = 8 * PARALLEL_DEFLATE_CHUNK_SIZE as u64;
+
+const PARALLEL_DEFLATE_CHUNK_SIZE: usize = 1_048_576;
+const DEFLATE_DICTIONARY_SIZE: usize = 32_768;
+
+pub struct DeflateCodec;
+
+impl Codec for DeflateCodec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::Deflate
+    }
+
+    fn decode(&self, reader: &mut dyn Read, writer: &mut dyn Write, _decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        let mut deflate_decoder = DeflateDecoder::new(reader);
+        std::io::copy(&mut deflate_decoder, writer)
+    }
+
+    fn decoder<'r>(&self, reader: Box<dyn Read + 'r>, _decode_info: &DecodeInfo) -> std::io::Result<Box<dyn Read + 'r>> {
+        Ok(Box::new(DeflateDecoder::new(reader)))
+    }
+
+    fn encode(&self, reader: &mut dyn BufRead, writer: &mut dyn Write, compression_options: &CompressionOptions) -> std::io::Result<u64> {
+        let mut deflate_encoder = DeflateEncoder::new(reader, Compression::new(compression_options.compression_level()));
+        std::io::copy(&mut deflate_encoder, writer)
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+}
+
+pub struct Deflate64Codec;
+
+impl Codec for Deflate64Codec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::Deflate64
+    }
+
+    fn decode(&self, reader: &mut dyn Read, writer: &mut dyn Write, _decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        let mut deflate64_decoder = Deflate64Decoder::new(reader);
+        std::io::copy(&mut deflate64_decoder, writer)
+    }
+
+    fn decoder<'r>(&self, reader: Box<dyn Read + 'r>, _decode_info: &DecodeInfo) -> std::io::Result<Box<dyn Read + 'r>> {
+        Ok(Box::new(Deflate64Decoder::new(reader)))
+    }
+}
+
+// Deflates the reader pigz-style: fixed size chunks are compressed on `threads` worker threads, each primed with the tail of
+// the previous chunk as dictionary, and joined by sync flushes into a single deflate stream. Returns the CRC32 of the input.
+// The output only depends on the chunk size, not on the number of threads.
+pub fn deflate_in_parallel<R, W>(reader: &mut R, writer: &mut W, compression_level: u32, threads: usize) -> std::io::Result<u32> where R: Read, W: Write {
+    let threads = threads.max(1);
+    // Chunks read ahead of the writer, which bounds the memory used.
+    let max_chunks_in_flight = 2 * threads;
+
+    let (chunk_sender, chunk_receiver) = mpsc::channel::<DeflateChunkJob>();
+    let chunk_receiver = Mutex::new(chunk_receiver);
+
+    thread::scope(|scope| {
+        // Owned by the scope, so that the workers stop once it is dropped, also on errors.
+        let chunk_sender = chunk_sender;
+        let (deflated_sender, deflated_receiver) = mpsc::channel();
+
+        for _ in 0..threads {
+            let (chunk_receiver, deflated_sender) = (&chunk_receiver, deflated_sender.clone());
+
+            scope.spawn(move || loop {
+                let job = match chunk_receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => break
+                };
+                let deflated_chunk = deflate_chunk(&job.chunk, &job.dictionary, compression_level, job.is_last_chunk)
+                    .map(|deflated_chunk| (deflated_chunk, crc32::checksum_ieee(&job.chunk), job.chunk.len() as u64));
+
+                if deflated_sender.send((job.index, deflated_chunk)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(deflated_sender);
+
+        let mut checksum = 0;
+        let mut pending = BTreeMap::new();
+        let mut next_to_write = 0;
+        let mut chunks_in_flight = 0;
+        let mut dictionary = Vec::new();
+        let mut next_chunk = read_chunk(reader)?;
+
+        for index in 0.. {
+            let chunk = next_chunk;
+            // A short chunk ends the input, otherwise the next one tells whether this is the last.
+            next_chunk = if chunk.len() < PARALLEL_DEFLATE_CHUNK_SIZE { Vec::new() } else { read_chunk(reader)? };
+            let is_last_chunk = next_chunk.is_empty();
+            let chunk_dictionary = std::mem::replace(&mut dictionary, dictionary_of(&chunk).to_vec());
+
+            chunk_sender.send(DeflateChunkJob { index, chunk, dictionary: chunk_dictionary, is_last_chunk })
+                .map_err(|_| Error::other("The deflate workers have stopped."))?;
+            chunks_in_flight += 1;
+
+            while chunks_in_flight >= max_chunks_in_flight || (is_last_chunk && chunks_in_flight > 0) {
+                let (chunk_index, deflated_chunk) = deflated_receiver.recv()
+                    .map_err(|_| Error::other("The deflate workers have stopped."))?;
+                pending.insert(chunk_index, deflated_chunk?);
+                chunks_in_flight -= 1;
+
+                while let Some((deflated_chunk, chunk_checksum, chunk_len)) = pending.remove(&next_to_write) {
+                    writer.write_all(&deflated_chunk)?;
+                    checksum = combine_checksums(checksum, chunk_checksum, chunk_len);
+                    next_to_write += 1;
+                }
+            }
+
+            if is_last_chunk {
+                break;
+            }
+        }
+
+        Ok(checksum)
+    })
+}
+
+struct DeflateChunkJob {
+    index: usize,
+    chunk: Vec<u8>,
+    dictionary: Vec<u8>,
+    is_last_chunk: bool
+}
+
+fn read_chunk<R>(reader: &mut R) -> std::io::Result<Vec<u8>> where R: Read {
+    let mut chunk = Vec::with_capacity(PARALLEL_DEFLATE_CHUNK_SIZE);
+    reader.take(PARALLEL_DEFLATE_CHUNK_SIZE as u64).read_to_end(&mut chunk)?;
+
+    Ok(chunk)
+}
+
+fn dictionary_of(chunk: &[u8]) -> &[u8] {
+    &chunk[chunk.len().saturating_sub(DEFLATE_DICTIONARY_SIZE)..]
+}
+
+fn deflate_chunk(chunk: &[u8], dictionary: &[u8], compression_level: u32, is_last_chunk: bool) -> std::io::Result<Vec<u8>> {
+    let mut compress = Compress::new(Compression::new(compression_level), false);
+
+    if !dictionary.is_empty() {
+        compress.set_dictionary(dictionary).map_err(Error::other)?;
+    }
+
+    let flush = if is_last_chunk { FlushCompress::Finish } else { FlushCompress::Sync };
+    let mut deflated_chunk = Vec::with_capacity(chunk.len() / 2 + 1024);
+    let mut input = chunk;
+
+    loop {
+        if deflated_chunk.capacity() - deflated_chunk.len() < 1024 {
+            deflated_chunk.reserve(PARALLEL_DEFLATE_CHUNK_SIZE / 4);
+        }
+
+        let total_in = compress.total_in();
+        let status = compress.compress_vec(input, &mut deflated_chunk, flush)
+            .map_err(Error::other)?;
+        input = &input[(compress.total_in() - total_in) as usize..];
+
+        let chunk_finished = if is_last_chunk {
+            status == Status::StreamEnd
+        } else {
+            input.is_empty() && deflated_chunk.len() < deflated_chunk.capacity()
+        };
+
+        if chunk_finished {
+            return Ok(deflated_chunk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use inflate::DeflateDecoder;
+    use zip::codec::legacy::BitWriter;
+
+    fn sample_data(size: usize) -> Vec<u8> {
+        (0..size).map(|index| ((index / 7) % 251) as u8 ^ (index % 13) as u8).collect()
+    }
+
+    fn inflate(deflated: &[u8]) -> Vec<u8> {
+        let mut inflated = Vec::new();
+        DeflateDecoder::new(Cursor::new(deflated)).read_to_end(&mut inflated).unwrap();
+        inflated
+    }
+
+    #[test]
+    fn parallel_deflate_produces_single_valid_stream() {
+        let data = sample_data(PARALLEL_DEFLATE_CHUNK_SIZE * 5 + 1234);
+        let mut deflated = Vec::new();
+
+        let checksum = deflate_in_parallel(&mut Cursor::new(&data), &mut deflated, 9, 3).unwrap();
+
+        assert_eq!(checksum, crc32::checksum_ieee(&data));
+        assert_eq!(inflate(&deflated), data);
+    }
+
+    #[test]
+    fn parallel_deflate_handles_chunk_aligned_and_empty_input() {
+        for size in &[0, PARALLEL_DEFLATE_CHUNK_SIZE, PARALLEL_DEFLATE_CHUNK_SIZE * 2] {
+            let data = sample_data(*size);
+            let mut deflated = Vec::new();
+
+            let checksum = deflate_in_parallel(&mut Cursor::new(&data), &mut deflated, 9, 2).unwrap();
+
+            assert_eq!(checksum, crc32::checksum_ieee(&data));
+            assert_eq!(inflate(&deflated), data);
+        }
+    }
+
+    #[test]
+    fn parallel_deflate_output_does_not_depend_on_thread_count() {
+        let data = sample_data(PARALLEL_DEFLATE_CHUNK_SIZE * 3 + 17);
+        let mut single_threaded = Vec::new();
+        let mut multi_threaded = Vec::new();
+
+        deflate_in_parallel(&mut Cursor::new(&data), &mut single_threaded, 9, 1).unwrap();
+        deflate_in_parallel(&mut Cursor::new(&data), &mut multi_threaded, 9, 4).unwrap();
+
+        assert_eq!(single_threaded, multi_threaded);
+    }
+
+    #[test]
+    fn deflate64_decodes_extended_length_code() {
+        let mut bit_writer = BitWriter::new();
+
+        // Final block with fixed Huffman codes.
+        bit_writer.write_bits(1, 1);
+        bit_writer.write_bits(1, 2);
+        // Literal 'a'.
+        bit_writer.write_code(0x30 + b'a' as u32, 8);
+        // Length code 285 has 16 extra bits in Deflate64: 3 + 997 = 1000 bytes at distance 1.
+        bit_writer.write_code(0xC0 + 5, 8);
+        bit_writer.write_bits(997, 16);
+        bit_writer.write_code(0, 5);
+        // End of block.
+        bit_writer.write_code(0, 7);
+
+        let mut decoded = Vec::new();
+        Deflate64Codec.decode(&mut Cursor::new(bit_writer.into_bytes()), &mut decoded, &DecodeInfo::new(0, 1001)).unwrap();
+
+        assert_eq!(decoded, vec![b'a'; 1001]);
+    }
+}
@@ -0,0 +1,147 @@
+use std::io::Read;
+
+use super::{BitReader, copy_from_history, invalid_data, output_buffer};
+
+// General purpose flag bits of imploded entries.
+const LARGE_DICTIONARY_FLAG: u16 = 0x02;
+const LITERAL_TREE_FLAG: u16 = 0x04;
+
+const MAX_CODE_LENGTH: usize = 16;
+
+// Shannon-Fano tree read as canonical Huffman codes. Implode writes the codes with their bits inverted.
+struct ShannonFanoTree {
+    code_length_counts: [usize; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>
+}
+
+impl ShannonFanoTree {
+    // Trees are stored as bytes, each giving a code length in the low nibble and how many symbols in a row use it in the high nibble.
+    fn from_bit_reader<R>(bit_reader: &mut BitReader<R>, symbol_count: usize) -> std::io::Result<Self> where R: Read {
+        let byte_count = bit_reader.read_bits(8)? as usize + 1;
+        let mut code_lengths = Vec::with_capacity(symbol_count);
+
+        for _ in 0..byte_count {
+            let byte = bit_reader.read_bits(8)? as usize;
+            let code_length = (byte & 0x0F) + 1;
+            let repeat = (byte >> 4) + 1;
+            code_lengths.extend(std::iter::repeat_n(code_length, repeat));
+        }
+
+        if code_lengths.len() != symbol_count {
+            return Err(invalid_data("Invalid Shannon-Fano tree in imploded data."));
+        }
+
+        let mut code_length_counts = [0; MAX_CODE_LENGTH + 1];
+        for code_length in &code_lengths {
+            code_length_counts[*code_length] += 1;
+        }
+
+        let mut symbols = Vec::with_capacity(symbol_count);
+        for code_length in 1..=MAX_CODE_LENGTH {
+            symbols.extend((0..symbol_count as u16).filter(|symbol| code_lengths[*symbol as usize] == code_length));
+        }
+
+        Ok(Self {
+            code_length_counts,
+            symbols
+        })
+    }
+
+    fn decode<R>(&self, bit_reader: &mut BitReader<R>) -> std::io::Result<u16> where R: Read {
+        let mut code = 0;
+        let mut first_code = 0;
+        let mut index = 0;
+
+        for code_length in 1..=MAX_CODE_LENGTH {
+            code |= bit_reader.read_bits(1)? ^ 1;
+            let count = self.code_length_counts[code_length] as u32;
+
+            if code >= first_code && code < first_code + count {
+                return Ok(self.symbols[index + (code - first_code) as usize]);
+            }
+
+            index += count as usize;
+            first_code = (first_code + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid_data("Invalid code in imploded data."))
+    }
+}
+
+// Implode (method 6) is LZ77 with Shannon-Fano coded lengths and distances, and optionally literals.
+pub fn explode<R>(reader: &mut R, general_purpose_flag: u16, uncompressed_size: u64) -> std::io::Result<Vec<u8>> where R: Read {
+    let mut bit_reader = BitReader::new(reader);
+    let mut output = output_buffer(uncompressed_size);
+
+    let literal_tree = if general_purpose_flag & LITERAL_TREE_FLAG != 0 {
+        Some(ShannonFanoTree::from_bit_reader(&mut bit_reader, 256)?)
+    } else {
+        None
+    };
+    let length_tree = ShannonFanoTree::from_bit_reader(&mut bit_reader, 64)?;
+    let distance_tree = ShannonFanoTree::from_bit_reader(&mut bit_reader, 64)?;
+
+    let distance_low_bits = if general_purpose_flag & LARGE_DICTIONARY_FLAG != 0 { 7 } else { 6 };
+    let min_match_length = if literal_tree.is_some() { 3 } else { 2 };
+
+    while (output.len() as u64) < uncompressed_size {
+        if bit_reader.read_bits(1)? == 1 {
+            let literal = match &literal_tree {
+                Some(literal_tree) => literal_tree.decode(&mut bit_reader)? as u8,
+                None => bit_reader.read_bits(8)? as u8
+            };
+            output.push(literal);
+        }
+        else {
+            let distance_low = bit_reader.read_bits(distance_low_bits)? as usize;
+            let distance_high = distance_tree.decode(&mut bit_reader)? as usize;
+            let distance = (distance_high << distance_low_bits | distance_low) + 1;
+
+            let mut length = length_tree.decode(&mut bit_reader)? as usize;
+            if length == 63 {
+                length += bit_reader.read_bits(8)? as usize;
+            }
+
+            copy_from_history(&mut output, distance, length + min_match_length);
+        }
+    }
+
+    output.truncate(uncompressed_size as usize);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::codec::legacy::BitWriter;
+
+    // All 64 symbols with 6 bit codes, 16 symbols per byte. The canonical code of each symbol is the symbol itself.
+    fn write_flat_tree(bit_writer: &mut BitWriter) {
+        bit_writer.write_bits(3, 8);
+        for _ in 0..4 {
+            bit_writer.write_bits(0xF5, 8);
+        }
+    }
+
+    #[test]
+    fn explodes_with_inverted_codes_and_raw_literals() {
+        let mut bit_writer = BitWriter::new();
+        write_flat_tree(&mut bit_writer);
+        write_flat_tree(&mut bit_writer);
+
+        // Literal 'a'.
+        bit_writer.write_bits(1, 1);
+        bit_writer.write_bits(b'a' as u32, 8);
+        // Match of 3 + 2 bytes at distance 1.
+        let inverted = |code: u32| !code & 0x3F;
+        bit_writer.write_bits(0, 1);
+        bit_writer.write_bits(0, 6);
+        bit_writer.write_code(inverted(0), 6);
+        bit_writer.write_code(inverted(3), 6);
+
+        let exploded = explode(&mut Cursor::new(bit_writer.into_bytes()), 0, 6).unwrap();
+        assert_eq!(exploded, b"aaaaaa");
+    }
+}
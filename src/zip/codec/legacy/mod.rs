@@ -0,0 +1,171 @@
+use std::io::{BufReader, Error, ErrorKind, Read, Write};
+
+use zip::mem_map::CompressionMethod;
+
+use super::{Codec, DecodeInfo};
+
+mod explode;
+mod unreduce;
+mod unshrink;
+
+use self::explode::explode;
+use self::unreduce::unreduce;
+use self::unshrink::unshrink;
+
+pub fn codecs() -> Vec<Box<dyn Codec>> {
+    vec![
+        Box::new(ShrinkCodec),
+        Box::new(ReduceCodec { compression_factor: 1 }),
+        Box::new(ReduceCodec { compression_factor: 2 }),
+        Box::new(ReduceCodec { compression_factor: 3 }),
+        Box::new(ReduceCodec { compression_factor: 4 }),
+        Box::new(ImplodeCodec)
+    ]
+}
+
+struct ShrinkCodec;
+
+impl Codec for ShrinkCodec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::Shrunk
+    }
+
+    fn decode(&self, mut reader: &mut dyn Read, writer: &mut dyn Write, decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        write_decoded(unshrink(&mut reader, decode_info.uncompressed_size())?, writer)
+    }
+}
+
+struct ReduceCodec {
+    compression_factor: u8
+}
+
+impl Codec for ReduceCodec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::from_addr(1 + self.compression_factor as u16)
+    }
+
+    fn decode(&self, mut reader: &mut dyn Read, writer: &mut dyn Write, decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        write_decoded(unreduce(&mut reader, self.compression_factor, decode_info.uncompressed_size())?, writer)
+    }
+}
+
+struct ImplodeCodec;
+
+impl Codec for ImplodeCodec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::Implode
+    }
+
+    fn decode(&self, mut reader: &mut dyn Read, writer: &mut dyn Write, decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        write_decoded(explode(&mut reader, decode_info.general_purpose_flag(), decode_info.uncompressed_size())?, writer)
+    }
+}
+
+// The output of the legacy PKZIP methods is decoded in memory. Shrunk entries refer back to any earlier part of the output,
+// and entries created with these methods are small anyway. The uncompressed size comes from the archive, so it only preallocates up to a limit.
+const MAX_PREALLOCATED_SIZE: u64 = 64 * 1024;
+
+fn output_buffer(uncompressed_size: u64) -> Vec<u8> {
+    Vec::with_capacity(uncompressed_size.min(MAX_PREALLOCATED_SIZE) as usize)
+}
+
+fn write_decoded(decoded: Vec<u8>, writer: &mut dyn Write) -> std::io::Result<u64> {
+    writer.write_all(&decoded)?;
+    Ok(decoded.len() as u64)
+}
+
+// Reads the compressed data as the bits are needed.
+pub struct BitReader<R> {
+    reader: BufReader<R>,
+    bits: u64,
+    bit_count: usize
+}
+
+impl<R> BitReader<R> where R: Read {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            bits: 0,
+            bit_count: 0
+        }
+    }
+
+    // Buffers up to 32 bits, false if the compressed data ends before.
+    pub fn has_bits(&mut self, count: usize) -> std::io::Result<bool> {
+        let mut byte = [0];
+        while self.bit_count < count {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(false),
+                Ok(_) => {
+                    self.bits |= (byte[0] as u64) << self.bit_count;
+                    self.bit_count += 8;
+                },
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err)
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Reads the given number of bits, least significant bit first.
+    pub fn read_bits(&mut self, count: usize) -> std::io::Result<u32> {
+        if !self.has_bits(count)? {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Compressed data ended unexpectedly."));
+        }
+
+        let value = (self.bits & ((1 << count) - 1)) as u32;
+        self.bits >>= count;
+        self.bit_count -= count;
+
+        Ok(value)
+    }
+}
+
+// Copies `length` bytes starting `distance` bytes back. Bytes before the start of the output are zeros.
+fn copy_from_history(output: &mut Vec<u8>, distance: usize, length: usize) {
+    for _ in 0..length {
+        let byte = if distance > output.len() { 0 } else { output[output.len() - distance] };
+        output.push(byte);
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    bit_count: usize
+}
+
+#[cfg(test)]
+impl BitWriter {
+    pub fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            bit_count: 0
+        }
+    }
+
+    pub fn write_bits(&mut self, value: u32, count: usize) {
+        for bit in 0..count {
+            if self.bit_count.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            self.bytes[self.bit_count / 8] |= (((value >> bit) & 1) as u8) << (self.bit_count % 8);
+            self.bit_count += 1;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit.
+    pub fn write_code(&mut self, code: u32, count: usize) {
+        let reversed = (0..count).fold(0, |reversed, bit| (reversed << 1) | ((code >> bit) & 1));
+        self.write_bits(reversed, count);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
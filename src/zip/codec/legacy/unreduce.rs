@@ -0,0 +1,123 @@
+use std::io::Read;
+
+use super::{BitReader, copy_from_history, invalid_data, output_buffer};
+
+const DLE: u8 = 144;
+const MAX_FOLLOWERS: usize = 64;
+
+enum ExpandState {
+    Literal,
+    AfterDle,
+    ExtraLength { length_byte: u8 },
+    Distance { length_byte: u8, length: usize }
+}
+
+// Reduce (methods 2 to 5) first predicts each byte from a set of likely followers of the previous byte,
+// then expands DLE escaped back references. The compression factor sets how many bits of a reference go to the length.
+pub fn unreduce<R>(reader: &mut R, compression_factor: u8, uncompressed_size: u64) -> std::io::Result<Vec<u8>> where R: Read {
+    let mut bit_reader = BitReader::new(reader);
+    let mut output = output_buffer(uncompressed_size);
+
+    let follower_sets = read_follower_sets(&mut bit_reader)?;
+    let length_mask = 0xFFu8 >> compression_factor;
+
+    let mut last_byte = 0u8;
+    let mut state = ExpandState::Literal;
+
+    while (output.len() as u64) < uncompressed_size {
+        let followers = &follower_sets[last_byte as usize];
+
+        let byte = if followers.is_empty() || bit_reader.read_bits(1)? == 1 {
+            bit_reader.read_bits(8)? as u8
+        }
+        else {
+            let index = bit_reader.read_bits(follower_index_bits(followers.len()))? as usize;
+            *followers.get(index).ok_or_else(|| invalid_data("Invalid follower index in reduced data."))?
+        };
+        last_byte = byte;
+
+        state = match state {
+            ExpandState::Literal if byte == DLE => ExpandState::AfterDle,
+            ExpandState::Literal => {
+                output.push(byte);
+                ExpandState::Literal
+            },
+            ExpandState::AfterDle if byte == 0 => {
+                output.push(DLE);
+                ExpandState::Literal
+            },
+            ExpandState::AfterDle if byte & length_mask == length_mask => ExpandState::ExtraLength { length_byte: byte },
+            ExpandState::AfterDle => ExpandState::Distance { length_byte: byte, length: (byte & length_mask) as usize },
+            ExpandState::ExtraLength { length_byte } => ExpandState::Distance { length_byte, length: length_mask as usize + byte as usize },
+            ExpandState::Distance { length_byte, length } => {
+                let distance = ((length_byte >> (8 - compression_factor)) as usize) * 256 + byte as usize + 1;
+                copy_from_history(&mut output, distance, length + 3);
+                ExpandState::Literal
+            }
+        };
+    }
+
+    output.truncate(uncompressed_size as usize);
+    Ok(output)
+}
+
+// Follower sets are stored from the one of byte 255 down to the one of byte 0.
+fn read_follower_sets<R>(bit_reader: &mut BitReader<R>) -> std::io::Result<Vec<Vec<u8>>> where R: Read {
+    let mut follower_sets = vec![Vec::new(); 256];
+
+    for followers in follower_sets.iter_mut().rev() {
+        let follower_count = bit_reader.read_bits(6)? as usize;
+        if follower_count >= MAX_FOLLOWERS {
+            return Err(invalid_data("Invalid follower set in reduced data."));
+        }
+
+        for _ in 0..follower_count {
+            followers.push(bit_reader.read_bits(8)? as u8);
+        }
+    }
+
+    Ok(follower_sets)
+}
+
+fn follower_index_bits(follower_count: usize) -> usize {
+    let mut bits = 1;
+    while (1 << bits) < follower_count {
+        bits += 1;
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::codec::legacy::BitWriter;
+
+    #[test]
+    fn unreduces_followers_and_back_references() {
+        let mut bit_writer = BitWriter::new();
+
+        // Only 'a' has a follower set, holding 'b'.
+        for byte in (0..=255u8).rev() {
+            if byte == b'a' {
+                bit_writer.write_bits(1, 6);
+                bit_writer.write_bits(b'b' as u32, 8);
+            }
+            else {
+                bit_writer.write_bits(0, 6);
+            }
+        }
+
+        // 'a', then 'b' as the first follower of 'a'.
+        bit_writer.write_bits(b'a' as u32, 8);
+        bit_writer.write_bits(0, 1);
+        bit_writer.write_bits(0, 1);
+        // A back reference of 2 + 3 bytes at distance 2, then an escaped DLE.
+        for byte in &[DLE, 2, 1, DLE, 0] {
+            bit_writer.write_bits(*byte as u32, 8);
+        }
+
+        let unreduced = unreduce(&mut Cursor::new(bit_writer.into_bytes()), 1, 8).unwrap();
+        assert_eq!(unreduced, b"abababa\x90");
+    }
+}
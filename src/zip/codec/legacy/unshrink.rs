@@ -0,0 +1,157 @@
+use std::io::Read;
+
+use super::{BitReader, invalid_data, output_buffer};
+#[cfg(test)]
+use super::MAX_PREALLOCATED_SIZE;
+
+const MIN_CODE_SIZE: usize = 9;
+const MAX_CODE_SIZE: usize = 13;
+const MAX_CODE: usize = (1 << MAX_CODE_SIZE) - 1;
+const CONTROL_CODE: usize = 256;
+const FIRST_FREE_CODE: usize = 257;
+const INCREASE_CODE_SIZE: u32 = 1;
+const PARTIAL_CLEAR: u32 = 2;
+
+#[derive(Clone, Copy)]
+struct CodeEntry {
+    prefix_code: Option<usize>,
+    length: usize,
+    // Any position in the output where the string of the code has been written.
+    output_position: usize
+}
+
+// Shrink (method 1) is LZW with code sizes from 9 to 13 bits. Instead of resetting the whole table when it is full,
+// the compressor can clear the codes that are not the prefix of any other code and these are reused in increasing order.
+pub fn unshrink<R>(reader: &mut R, uncompressed_size: u64) -> std::io::Result<Vec<u8>> where R: Read {
+    let mut bit_reader = BitReader::new(reader);
+    let mut output = output_buffer(uncompressed_size);
+
+    let mut code_table = vec![CodeEntry { prefix_code: None, length: 1, output_position: 0 }; MAX_CODE + 1];
+    let mut free_codes: Vec<usize> = (FIRST_FREE_CODE..=MAX_CODE).rev().collect();
+    let mut code_size = MIN_CODE_SIZE;
+
+    if !bit_reader.has_bits(code_size)? || uncompressed_size == 0 {
+        return Ok(output);
+    }
+
+    let mut previous_code = bit_reader.read_bits(code_size)? as usize;
+    if previous_code >= CONTROL_CODE {
+        return Err(invalid_data("Shrunk data starts with an invalid code."));
+    }
+    code_table[previous_code].output_position = 0;
+    output.push(previous_code as u8);
+
+    while (output.len() as u64) < uncompressed_size && bit_reader.has_bits(code_size)? {
+        let code = bit_reader.read_bits(code_size)? as usize;
+
+        if code == CONTROL_CODE {
+            match bit_reader.read_bits(code_size)? {
+                INCREASE_CODE_SIZE if code_size < MAX_CODE_SIZE => code_size += 1,
+                PARTIAL_CLEAR => partial_clear(&mut code_table, &mut free_codes),
+                _ => return Err(invalid_data("Invalid control code in shrunk data."))
+            }
+            continue;
+        }
+
+        let previous_entry = code_table[previous_code];
+        let new_entry = CodeEntry {
+            prefix_code: Some(previous_code),
+            length: previous_entry.length + 1,
+            output_position: previous_entry.output_position
+        };
+
+        // The code added after this one may be used right away, its string is the previous string followed by its own first byte.
+        let next_free_code = free_codes.last().copied();
+        if Some(code) == next_free_code {
+            code_table[code] = new_entry;
+        }
+        else if code > CONTROL_CODE && code_table[code].prefix_code.is_none() {
+            return Err(invalid_data("Shrunk data refers to an unknown code."));
+        }
+
+        let entry = code_table[code];
+        let output_position = output.len();
+        if code < CONTROL_CODE {
+            output.push(code as u8);
+        }
+        else {
+            for index in 0..entry.length {
+                let byte = output[entry.output_position + index];
+                output.push(byte);
+            }
+        }
+        code_table[code].output_position = output_position;
+
+        if let Some(next_free_code) = next_free_code {
+            free_codes.pop();
+            code_table[next_free_code] = new_entry;
+        }
+
+        previous_code = code;
+    }
+
+    output.truncate(uncompressed_size as usize);
+    Ok(output)
+}
+
+fn partial_clear(code_table: &mut [CodeEntry], free_codes: &mut Vec<usize>) {
+    let mut is_prefix = vec![false; MAX_CODE + 1];
+    for entry in &code_table[FIRST_FREE_CODE..] {
+        if let Some(prefix_code) = entry.prefix_code {
+            is_prefix[prefix_code] = true;
+        }
+    }
+
+    for (code, entry) in code_table.iter_mut().enumerate().skip(FIRST_FREE_CODE) {
+        if !is_prefix[code] {
+            entry.prefix_code = None;
+        }
+    }
+
+    *free_codes = (FIRST_FREE_CODE..=MAX_CODE).rev()
+        .filter(|code| code_table[*code].prefix_code.is_none())
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::codec::legacy::BitWriter;
+
+    fn shrunk(codes: &[(u32, usize)]) -> Vec<u8> {
+        let mut bit_writer = BitWriter::new();
+        for (code, code_size) in codes {
+            bit_writer.write_bits(*code, *code_size);
+        }
+        bit_writer.into_bytes()
+    }
+
+    #[test]
+    fn unshrinks_codes_added_to_the_table() {
+        let data = shrunk(&[(97, 9), (98, 9), (257, 9), (257, 9)]);
+        assert_eq!(unshrink(&mut Cursor::new(data), 6).unwrap(), b"ababab");
+    }
+
+    #[test]
+    fn unshrinks_code_used_before_being_added() {
+        let data = shrunk(&[(97, 9), (257, 9), (97, 9)]);
+        assert_eq!(unshrink(&mut Cursor::new(data), 4).unwrap(), b"aaaa");
+    }
+
+    #[test]
+    fn unshrinks_after_code_size_increase_and_partial_clear() {
+        // "ab" is added as 257 and freed by the partial clear as it is not the prefix of another code.
+        // 257 is then reused for "bc".
+        let data = shrunk(&[(97, 9), (98, 9), (256, 9), (1, 9), (256, 10), (2, 10), (99, 10), (257, 10)]);
+        assert_eq!(unshrink(&mut Cursor::new(data), 5).unwrap(), b"abcbc");
+    }
+
+    #[test]
+    fn preallocates_only_up_to_a_limit() {
+        let data = shrunk(&[(97, 9), (98, 9), (257, 9), (257, 9)]);
+        let output = unshrink(&mut Cursor::new(data), u64::MAX).unwrap();
+        assert_eq!(output, b"ababab");
+        assert!(output.capacity() <= MAX_PREALLOCATED_SIZE as usize);
+    }
+}
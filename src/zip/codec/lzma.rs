@@ -0,0 +1,176 @@
+use std::io::{BufRead, Cursor, Error, ErrorKind, Read, Write};
+
+use byteorder::{ByteOrder, LittleEndian};
+use xz2::{bufread::XzEncoder, read::XzDecoder, stream::{LzmaOptions, Stream}};
+
+use zip::{mem_map::{CompressionMethod, LZMA_EOS_MARKER_FLAG}, options::CompressionOptions};
+
+use super::{Codec, DecodeInfo};
+
+// LZMA entries start with the version of the LZMA SDK, the size of the properties and the properties themselves.
+// The .lzma (LZMA alone) format liblzma works with starts with the same properties followed by the uncompressed size.
+const LZMA_SDK_VERSION: [u8; 2] = [5, 2];
+const LZMA_PROPERTIES_SIZE: usize = 5;
+const LZMA_ALONE_HEADER_SIZE: usize = LZMA_PROPERTIES_SIZE + 8;
+const UNKNOWN_UNCOMPRESSED_SIZE: u64 = u64::MAX;
+
+// Like the zstd window limit, an untrusted entry can not make the decoder allocate more than 128 MiB for its dictionary.
+// The presets of xz and 7-Zip use dictionaries of up to 64 MiB.
+pub const DEFAULT_LZMA_MEMORY_LIMIT: u64 = 128 * 1024 * 1024;
+
+pub struct LzmaCodec {
+    memory_limit: u64
+}
+
+impl LzmaCodec {
+    pub fn new(memory_limit: u64) -> Self {
+        Self {
+            memory_limit
+        }
+    }
+}
+
+impl Codec for LzmaCodec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::LZMA
+    }
+
+    fn decode(&self, reader: &mut dyn Read, writer: &mut dyn Write, decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        let mut lzma_decoder = lzma_decoder(reader, decode_info.general_purpose_flag(), decode_info.uncompressed_size(), self.memory_limit)?;
+        std::io::copy(&mut lzma_decoder, writer)
+    }
+
+    fn decoder<'r>(&self, reader: Box<dyn Read + 'r>, decode_info: &DecodeInfo) -> std::io::Result<Box<dyn Read + 'r>> {
+        Ok(Box::new(lzma_decoder(reader, decode_info.general_purpose_flag(), decode_info.uncompressed_size(), self.memory_limit)?))
+    }
+
+    fn encode(&self, reader: &mut dyn BufRead, writer: &mut dyn Write, compression_options: &CompressionOptions) -> std::io::Result<u64> {
+        lzma_encode(reader, writer, compression_options.compression_level())
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+}
+
+pub struct XzCodec {
+    memory_limit: u64
+}
+
+impl XzCodec {
+    pub fn new(memory_limit: u64) -> Self {
+        Self {
+            memory_limit
+        }
+    }
+}
+
+impl Codec for XzCodec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::XZ
+    }
+
+    fn decode(&self, reader: &mut dyn Read, writer: &mut dyn Write, _decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        let mut xz_decoder = XzDecoder::new_stream(reader, xz_stream_decoder(self.memory_limit)?);
+        std::io::copy(&mut xz_decoder, writer)
+    }
+
+    fn decoder<'r>(&self, reader: Box<dyn Read + 'r>, _decode_info: &DecodeInfo) -> std::io::Result<Box<dyn Read + 'r>> {
+        Ok(Box::new(XzDecoder::new_stream(reader, xz_stream_decoder(self.memory_limit)?)))
+    }
+
+    fn encode(&self, reader: &mut dyn BufRead, writer: &mut dyn Write, compression_options: &CompressionOptions) -> std::io::Result<u64> {
+        let mut xz_encoder = XzEncoder::new(reader, compression_options.compression_level().min(9));
+        std::io::copy(&mut xz_encoder, writer)
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+}
+
+fn lzma_encode<R, W>(reader: &mut R, writer: &mut W, compression_level: u32) -> std::io::Result<u64> where R: BufRead + ?Sized, W: Write + ?Sized {
+    let lzma_options = LzmaOptions::new_preset(compression_level.min(9)).map_err(Error::other)?;
+    let stream = Stream::new_lzma_encoder(&lzma_options).map_err(Error::other)?;
+    let mut lzma_encoder = XzEncoder::new_stream(reader, stream);
+
+    // The LZMA alone encoder always writes an end of stream marker and an unknown uncompressed size.
+    let mut alone_header = [0u8; LZMA_ALONE_HEADER_SIZE];
+    lzma_encoder.read_exact(&mut alone_header)?;
+
+    let mut zip_header = [0u8; 4];
+    zip_header[..2].copy_from_slice(&LZMA_SDK_VERSION);
+    LittleEndian::write_u16(&mut zip_header[2..], LZMA_PROPERTIES_SIZE as u16);
+
+    writer.write_all(&zip_header)?;
+    writer.write_all(&alone_header[..LZMA_PROPERTIES_SIZE])?;
+    let written = std::io::copy(&mut lzma_encoder, writer)?;
+
+    Ok((zip_header.len() + LZMA_PROPERTIES_SIZE) as u64 + written)
+}
+
+// Streams without an end of stream marker can only be decoded when the uncompressed size is known.
+fn lzma_decoder<'a, R>(mut reader: R, general_purpose_flag: u16, uncompressed_size: u64, memory_limit: u64) -> std::io::Result<impl Read + 'a> where R: Read + 'a {
+    let mut zip_header = [0u8; 4];
+    reader.read_exact(&mut zip_header)?;
+
+    let properties_size = LittleEndian::read_u16(&zip_header[2..]) as usize;
+    if properties_size != LZMA_PROPERTIES_SIZE {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Invalid LZMA properties size {}.", properties_size)));
+    }
+
+    let mut alone_header = [0u8; LZMA_ALONE_HEADER_SIZE];
+    reader.read_exact(&mut alone_header[..LZMA_PROPERTIES_SIZE])?;
+
+    let uncompressed_size = if general_purpose_flag & LZMA_EOS_MARKER_FLAG != 0 { UNKNOWN_UNCOMPRESSED_SIZE } else { uncompressed_size };
+    LittleEndian::write_u64(&mut alone_header[LZMA_PROPERTIES_SIZE..], uncompressed_size);
+
+    let stream = Stream::new_lzma_decoder(memory_limit).map_err(Error::other)?;
+    Ok(XzDecoder::new_stream(Cursor::new(alone_header).chain(reader), stream))
+}
+
+// Decodes concatenated streams like `XzDecoder::new` does.
+fn xz_stream_decoder(memory_limit: u64) -> std::io::Result<Stream> {
+    Stream::new_stream_decoder(memory_limit, xz2::stream::CONCATENATED).map_err(Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn lzma_and_xz_encoded_data_decodes_back() {
+        let data: Vec<u8> = (0..100_000).map(|index| ((index / 7) % 251) as u8).collect();
+        let codecs: [&dyn Codec; 2] = [&LzmaCodec::new(DEFAULT_LZMA_MEMORY_LIMIT), &XzCodec::new(DEFAULT_LZMA_MEMORY_LIMIT)];
+
+        for codec in &codecs {
+            let mut encoded = Vec::new();
+            let mut decoded = Vec::new();
+
+            codec.encode(&mut Cursor::new(&data), &mut encoded, &CompressionOptions::default()).unwrap();
+            codec.decode(&mut Cursor::new(&encoded), &mut decoded, &DecodeInfo::new(LZMA_EOS_MARKER_FLAG, data.len() as u64)).unwrap();
+
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn rejects_dictionaries_above_memory_limit() {
+        let data = b"Dictionary ".repeat(1000);
+        let mut encoded = Vec::new();
+        LzmaCodec::new(DEFAULT_LZMA_MEMORY_LIMIT).encode(&mut Cursor::new(&data), &mut encoded, &CompressionOptions::default()).unwrap();
+        let decode_info = DecodeInfo::new(LZMA_EOS_MARKER_FLAG, data.len() as u64);
+
+        // Declares a dictionary of 1 GiB in the properties after the 4 byte ZIP header and the 1 byte of literal and position bits.
+        let mut oversized = encoded.clone();
+        LittleEndian::write_u32(&mut oversized[5..9], 1 << 30);
+        let err = LzmaCodec::new(DEFAULT_LZMA_MEMORY_LIMIT).decode(&mut Cursor::new(&oversized), &mut Vec::new(), &decode_info).unwrap_err();
+        assert!(err.to_string().contains("memory"), "{}", err);
+
+        let mut decoded = Vec::new();
+        LzmaCodec::new(DEFAULT_LZMA_MEMORY_LIMIT).decode(&mut Cursor::new(&encoded), &mut decoded, &decode_info).unwrap();
+        assert_eq!(decoded, data);
+        assert!(LzmaCodec::new(1024 * 1024).decode(&mut Cursor::new(&encoded), &mut Vec::new(), &decode_info).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::BTreeMap, fmt, io::{BufRead, Cursor, Error, ErrorKind, Read, Write}};
+
+use super::{mem_map::CompressionMethod, options::CompressionOptions};
+
+mod store;
+pub(crate) mod legacy;
+#[cfg(feature = "deflate")]
+pub(crate) mod deflate;
+#[cfg(feature = "bzip2")]
+mod bzip2;
+#[cfg(feature = "zstd")]
+mod zstd;
+#[cfg(feature = "lzma")]
+mod lzma;
+
+// What a codec may need to know about the item it decodes.
+pub struct DecodeInfo {
+    general_purpose_flag: u16,
+    uncompressed_size: u64
+}
+
+impl DecodeInfo {
+    pub fn new(general_purpose_flag: u16, uncompressed_size: u64) -> Self {
+        Self {
+            general_purpose_flag,
+            uncompressed_size
+        }
+    }
+
+    pub fn general_purpose_flag(&self) -> u16 {
+        self.general_purpose_flag
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+}
+
+pub trait Codec: Send + Sync {
+    // The compression method the codec is registered for, its ID is the one written to the headers.
+    fn compression_method(&self) -> CompressionMethod;
+
+    // Decompresses the whole content of an item from the reader into the writer, returns the number of bytes written.
+    fn decode(&self, reader: &mut dyn Read, writer: &mut dyn Write, decode_info: &DecodeInfo) -> std::io::Result<u64>;
+
+    // Returns a reader decompressing the content of an item as it is read.
+    // The default decodes the whole content into memory first, codecs built on streaming decoders override it.
+    fn decoder<'r>(&self, mut reader: Box<dyn Read + 'r>, decode_info: &DecodeInfo) -> std::io::Result<Box<dyn Read + 'r>> {
+        let mut content = Vec::new();
+        self.decode(&mut reader, &mut content, decode_info)?;
+        Ok(Box::new(Cursor::new(content)))
+    }
+
+    // Compresses the reader into the writer. Codecs that can only decompress keep the default.
+    fn encode(&self, _reader: &mut dyn BufRead, _writer: &mut dyn Write, _compression_options: &CompressionOptions) -> std::io::Result<u64> {
+        Err(Error::new(ErrorKind::Unsupported, format!("Compressing with {:?} is not supported.", self.compression_method())))
+    }
+
+    fn can_encode(&self) -> bool {
+        false
+    }
+}
+
+// Codecs keyed by the ID of their compression method.
+pub struct CodecRegistry {
+    codecs: BTreeMap<u16, Box<dyn Codec>>
+}
+
+impl CodecRegistry {
+    pub fn empty() -> Self {
+        Self {
+            codecs: BTreeMap::new()
+        }
+    }
+
+    // Registers the codec, replacing the codec already registered for the same compression method.
+    pub fn register(&mut self, codec: Box<dyn Codec>) {
+        self.codecs.insert(codec.compression_method().to_addr(), codec);
+    }
+
+    pub fn codec(&self, compression_method: CompressionMethod) -> Option<&dyn Codec> {
+        self.codecs.get(&compression_method.to_addr())
+            .map(|codec| codec.as_ref())
+    }
+
+    pub fn encoder(&self, compression_method: CompressionMethod) -> Option<&dyn Codec> {
+        self.codec(compression_method)
+            .filter(|codec| codec.can_encode())
+    }
+
+    // Zstd entries are decoded with windows of up to 2^27 bytes. Entries zipped with a larger long distance window
+    // need the limit to be raised, the decoder then allocates as much memory as the entry asks for up to it.
+    #[cfg(feature = "zstd")]
+    pub fn set_zstd_window_log_max(&mut self, window_log_max: u32) {
+        self.register(Box::new(zstd::ZstdCodec::new(window_log_max)));
+    }
+
+    // LZMA and XZ entries are decoded with up to 128 MiB of memory, most of it for the dictionary the entry declares.
+    // Entries with larger dictionaries need the limit, given in bytes, to be raised.
+    #[cfg(feature = "lzma")]
+    pub fn set_lzma_memory_limit(&mut self, memory_limit: u64) {
+        self.register(Box::new(lzma::LzmaCodec::new(memory_limit)));
+        self.register(Box::new(lzma::XzCodec::new(memory_limit)));
+    }
+}
+
+// The built-in codecs of the enabled cargo features. Storing and the legacy PKZIP methods are always available.
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        let mut codec_registry = CodecRegistry::empty();
+
+        codec_registry.register(Box::new(store::StoreCodec));
+        for codec in legacy::codecs() {
+            codec_registry.register(codec);
+        }
+
+        #[cfg(feature = "deflate")]
+        {
+            codec_registry.register(Box::new(deflate::DeflateCodec));
+            codec_registry.register(Box::new(deflate::Deflate64Codec));
+        }
+        #[cfg(feature = "bzip2")]
+        codec_registry.register(Box::new(bzip2::Bzip2Codec));
+        #[cfg(feature = "zstd")]
+        codec_registry.register(Box::new(zstd::ZstdCodec::new(zstd::DEFAULT_ZSTD_WINDOW_LOG_MAX)));
+        #[cfg(feature = "lzma")]
+        {
+            codec_registry.register(Box::new(lzma::LzmaCodec::new(lzma::DEFAULT_LZMA_MEMORY_LIMIT)));
+            codec_registry.register(Box::new(lzma::XzCodec::new(lzma::DEFAULT_LZMA_MEMORY_LIMIT)));
+        }
+
+        codec_registry
+    }
+}
+
+impl fmt::Debug for CodecRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.codecs.values().map(|codec| codec.compression_method()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    struct InvertCodec;
+
+    impl Codec for InvertCodec {
+        fn compression_method(&self) -> CompressionMethod {
+            CompressionMethod::from_addr(0x4242)
+        }
+
+        fn decode(&self, reader: &mut dyn Read, writer: &mut dyn Write, _decode_info: &DecodeInfo) -> std::io::Result<u64> {
+            let mut content = Vec::new();
+            reader.read_to_end(&mut content)?;
+            writer.write_all(&content.iter().map(|byte| !byte).collect::<Vec<u8>>())?;
+            Ok(content.len() as u64)
+        }
+    }
+
+    #[test]
+    fn registered_codec_is_found_by_method_id() {
+        let mut codec_registry = CodecRegistry::default();
+        assert!(codec_registry.codec(CompressionMethod::from_addr(0x4242)).is_none());
+
+        codec_registry.register(Box::new(InvertCodec));
+
+        let codec = codec_registry.codec(CompressionMethod::from_addr(0x4242)).unwrap();
+        let mut decoded = Vec::new();
+        codec.decode(&mut Cursor::new(vec![0x00, 0xFF]), &mut decoded, &DecodeInfo::new(0, 2)).unwrap();
+
+        assert_eq!(decoded, vec![0xFF, 0x00]);
+        assert!(codec_registry.encoder(CompressionMethod::from_addr(0x4242)).is_none());
+    }
+
+    #[test]
+    fn default_registry_stores_and_decodes_legacy_methods() {
+        let codec_registry = CodecRegistry::default();
+
+        assert!(codec_registry.encoder(CompressionMethod::NoCompression).is_some());
+        assert!(codec_registry.codec(CompressionMethod::Implode).is_some());
+        assert!(codec_registry.encoder(CompressionMethod::Implode).is_none());
+    }
+}
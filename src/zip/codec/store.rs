@@ -0,0 +1,29 @@
+use std::io::{BufRead, Read, Write};
+
+use zip::{mem_map::CompressionMethod, options::CompressionOptions};
+
+use super::{Codec, DecodeInfo};
+
+pub struct StoreCodec;
+
+impl Codec for StoreCodec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::NoCompression
+    }
+
+    fn decode(&self, reader: &mut dyn Read, writer: &mut dyn Write, _decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        std::io::copy(reader, writer)
+    }
+
+    fn decoder<'r>(&self, reader: Box<dyn Read + 'r>, _decode_info: &DecodeInfo) -> std::io::Result<Box<dyn Read + 'r>> {
+        Ok(reader)
+    }
+
+    fn encode(&self, reader: &mut dyn BufRead, writer: &mut dyn Write, _compression_options: &CompressionOptions) -> std::io::Result<u64> {
+        std::io::copy(reader, writer)
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,93 @@
+use std::io::{BufRead, Read, Write};
+
+use zstd::stream::{read::Decoder, write::Encoder};
+
+use zip::{mem_map::CompressionMethod, options::CompressionOptions};
+
+use super::{Codec, DecodeInfo};
+
+// The window limit of zstd itself, an untrusted entry can not make the decoder allocate more than 128 MiB for its window.
+pub const DEFAULT_ZSTD_WINDOW_LOG_MAX: u32 = 27;
+
+pub struct ZstdCodec {
+    window_log_max: u32
+}
+
+impl ZstdCodec {
+    pub fn new(window_log_max: u32) -> Self {
+        Self {
+            window_log_max
+        }
+    }
+}
+
+impl Codec for ZstdCodec {
+    fn compression_method(&self) -> CompressionMethod {
+        CompressionMethod::ZStandard
+    }
+
+    fn decode(&self, reader: &mut dyn Read, writer: &mut dyn Write, _decode_info: &DecodeInfo) -> std::io::Result<u64> {
+        let mut zstd_decoder = Decoder::new(reader)?;
+        zstd_decoder.window_log_max(self.window_log_max)?;
+        std::io::copy(&mut zstd_decoder, writer)
+    }
+
+    fn decoder<'r>(&self, reader: Box<dyn Read + 'r>, _decode_info: &DecodeInfo) -> std::io::Result<Box<dyn Read + 'r>> {
+        let mut zstd_decoder = Decoder::new(reader)?;
+        zstd_decoder.window_log_max(self.window_log_max)?;
+        Ok(Box::new(zstd_decoder))
+    }
+
+    fn encode(&self, reader: &mut dyn BufRead, writer: &mut dyn Write, compression_options: &CompressionOptions) -> std::io::Result<u64> {
+        let zstd_options = compression_options.zstd_options();
+        let mut zstd_encoder = Encoder::new(writer, zstd_options.level())?;
+
+        if let Some(window_log) = zstd_options.long_distance_window_log() {
+            zstd_encoder.long_distance_matching(true)?;
+            zstd_encoder.window_log(window_log)?;
+        }
+
+        let written = std::io::copy(reader, &mut zstd_encoder)?;
+        zstd_encoder.finish()?;
+        Ok(written)
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::options::ZstdOptions;
+
+    #[test]
+    fn zstd_encoded_data_decodes_back_with_long_distance_window() {
+        let data: Vec<u8> = (0..100_000).map(|index| ((index / 7) % 251) as u8).collect();
+        let compression_options = CompressionOptions::new(Some(CompressionMethod::ZStandard), 9, vec![], ZstdOptions::new(19, Some(27)));
+        let mut encoded = Vec::new();
+        let mut decoded = Vec::new();
+
+        ZstdCodec::new(DEFAULT_ZSTD_WINDOW_LOG_MAX).encode(&mut Cursor::new(&data), &mut encoded, &compression_options).unwrap();
+        ZstdCodec::new(DEFAULT_ZSTD_WINDOW_LOG_MAX).decode(&mut Cursor::new(&encoded), &mut decoded, &DecodeInfo::new(0, data.len() as u64)).unwrap();
+
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn larger_windows_are_only_decoded_once_allowed() {
+        let data = b"Long distance ".repeat(1000);
+        let compression_options = CompressionOptions::new(Some(CompressionMethod::ZStandard), 9, vec![], ZstdOptions::new(3, Some(28)));
+        let mut encoded = Vec::new();
+        ZstdCodec::new(DEFAULT_ZSTD_WINDOW_LOG_MAX).encode(&mut Cursor::new(&data), &mut encoded, &compression_options).unwrap();
+        let decode_info = DecodeInfo::new(0, data.len() as u64);
+
+        assert!(ZstdCodec::new(DEFAULT_ZSTD_WINDOW_LOG_MAX).decode(&mut Cursor::new(&encoded), &mut Vec::new(), &decode_info).is_err());
+
+        let mut decoded = Vec::new();
+        ZstdCodec::new(28).decode(&mut Cursor::new(&encoded), &mut decoded, &decode_info).unwrap();
+        assert_eq!(decoded, data);
+    }
+}
@@ -0,0 +1,311 @@
+use std::{collections::BTreeMap, fs::File, io::{Cursor, Read, Seek, SeekFrom, Write}, sync::{Condvar, Mutex, mpsc}, thread};
+
+use super::ZipError;
+
+const SPILL_THRESHOLD: usize = 16 * 1_048_576;
+const JOBS_PER_THREAD: usize = 4;
+
+pub enum CompressedContent {
+    Memory(Vec<u8>),
+    Spilled(File)
+}
+
+impl CompressedContent {
+    pub fn len(&self) -> std::io::Result<u64> {
+        match self {
+            CompressedContent::Memory(buffer) => Ok(buffer.len() as u64),
+            CompressedContent::Spilled(file) => file.metadata().map(|metadata| metadata.len())
+        }
+    }
+
+    pub fn copy_to<W>(self, writer: &mut W) -> std::io::Result<u64> where W: Write {
+        match self {
+            CompressedContent::Memory(buffer) => {
+                writer.write_all(&buffer)?;
+                Ok(buffer.len() as u64)
+            },
+            CompressedContent::Spilled(mut file) => {
+                file.seek(SeekFrom::Start(0))?;
+                std::io::copy(&mut file, writer)
+            }
+        }
+    }
+
+    pub fn into_reader(self) -> std::io::Result<Box<dyn Read>> {
+        match self {
+            CompressedContent::Memory(buffer) => Ok(Box::new(Cursor::new(buffer))),
+            CompressedContent::Spilled(mut file) => {
+                file.seek(SeekFrom::Start(0))?;
+                Ok(Box::new(file))
+            }
+        }
+    }
+}
+
+// Keeps compressed data in memory and moves it into an anonymous temp file once it grows past SPILL_THRESHOLD.
+pub struct CompressedContentWriter {
+    buffer: Vec<u8>,
+    spill_file: Option<File>
+}
+
+impl CompressedContentWriter {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            spill_file: None
+        }
+    }
+
+    pub fn finish(mut self) -> std::io::Result<CompressedContent> {
+        self.flush()?;
+
+        Ok(match self.spill_file {
+            Some(file) => CompressedContent::Spilled(file),
+            None => CompressedContent::Memory(self.buffer)
+        })
+    }
+}
+
+impl Write for CompressedContentWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(file) = &mut self.spill_file {
+            return file.write(buf);
+        }
+
+        self.buffer.extend_from_slice(buf);
+
+        if self.buffer.len() > SPILL_THRESHOLD {
+            let mut file = tempfile::tempfile()?;
+            file.write_all(&self.buffer)?;
+            self.buffer = Vec::new();
+            self.spill_file = Some(file);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.spill_file {
+            Some(file) => file.flush(),
+            None => Ok(())
+        }
+    }
+}
+
+// Limits the number of threads compressing at once. Every pool worker holds a permit while compressing an item, large
+// items borrow the permits of idle workers to compress their chunks, so the threads of both never exceed the limit.
+pub struct ThreadBudget {
+    available: Mutex<usize>,
+    released: Condvar
+}
+
+impl ThreadBudget {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            available: Mutex::new(threads),
+            released: Condvar::new()
+        }
+    }
+
+    // Waits for a permit.
+    pub fn acquire(&self) -> ThreadPermits<'_> {
+        let mut available = self.available.lock().unwrap();
+        while *available == 0 {
+            available = self.released.wait(available).unwrap();
+        }
+        *available -= 1;
+
+        ThreadPermits { budget: self, count: 1 }
+    }
+
+    // Takes up to `max_count` permits without waiting, possibly none.
+    #[cfg(feature = "deflate")]
+    pub fn try_acquire(&self, max_count: usize) -> ThreadPermits<'_> {
+        let mut available = self.available.lock().unwrap();
+        let count = max_count.min(*available);
+        *available -= count;
+
+        ThreadPermits { budget: self, count }
+    }
+}
+
+// Permits of a ThreadBudget, given back once dropped.
+pub struct ThreadPermits<'a> {
+    budget: &'a ThreadBudget,
+    count: usize
+}
+
+impl<'a> ThreadPermits<'a> {
+    #[cfg(feature = "deflate")]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<'a> Drop for ThreadPermits<'a> {
+    fn drop(&mut self) {
+        *self.budget.available.lock().unwrap() += self.count;
+        self.budget.released.notify_all();
+    }
+}
+
+struct PoolState {
+    next_job: usize,
+    next_to_consume: usize,
+    aborted: bool
+}
+
+pub struct CompressionPool {
+    threads: usize,
+    thread_budget: ThreadBudget
+}
+
+impl CompressionPool {
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+
+        Self {
+            threads,
+            thread_budget: ThreadBudget::new(threads)
+        }
+    }
+
+    // Shared by the workers and the jobs they run.
+    pub fn thread_budget(&self) -> &ThreadBudget {
+        &self.thread_budget
+    }
+
+    // Runs `compress` for every job on the worker threads and hands the results to `consume` in job order.
+    // Workers never get more than a few jobs ahead of the consumer, so at most that many results are held at once.
+    pub fn run<J, T, C, F>(&self, jobs: &[J], compress: C, mut consume: F) -> Result<(), ZipError>
+    where J: Sync,
+          T: Send,
+          C: Fn(&J) -> Result<T, ZipError> + Sync,
+          F: FnMut(usize, T) -> Result<(), ZipError> {
+
+        let window = self.threads * JOBS_PER_THREAD;
+        let state = Mutex::new(PoolState { next_job: 0, next_to_consume: 0, aborted: false });
+        let job_available = Condvar::new();
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+
+            for _ in 0..self.threads {
+                let sender = sender.clone();
+                let (state, job_available, compress, thread_budget) = (&state, &job_available, &compress, &self.thread_budget);
+
+                scope.spawn(move || loop {
+                    let job_index = {
+                        let mut state = state.lock().unwrap();
+                        while !state.aborted && state.next_job < jobs.len() && state.next_job >= state.next_to_consume + window {
+                            state = job_available.wait(state).unwrap();
+                        }
+                        if state.aborted || state.next_job >= jobs.len() {
+                            break;
+                        }
+                        state.next_job += 1;
+                        state.next_job - 1
+                    };
+
+                    let result = {
+                        let _permit = thread_budget.acquire();
+                        compress(&jobs[job_index])
+                    };
+                    if sender.send((job_index, result)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+
+            let mut pending = BTreeMap::new();
+            let mut next_to_consume = 0;
+
+            for (job_index, result) in receiver.iter() {
+                pending.insert(job_index, result);
+
+                while let Some(result) = pending.remove(&next_to_consume) {
+                    if let Err(err) = result.and_then(|content| consume(next_to_consume, content)) {
+                        state.lock().unwrap().aborted = true;
+                        job_available.notify_all();
+                        return Err(err);
+                    }
+
+                    next_to_consume += 1;
+                    state.lock().unwrap().next_to_consume = next_to_consume;
+                    job_available.notify_all();
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::ZipErrorKind;
+
+    #[test]
+    fn consumes_results_in_job_order() {
+        let jobs: Vec<usize> = (0..100).collect();
+        let mut consumed = Vec::new();
+
+        CompressionPool::new(8).run(&jobs,
+            |job| Ok(CompressedContent::Memory(vec![*job as u8])),
+            |index, content| {
+                let mut buf = Vec::new();
+                content.copy_to(&mut buf).map_err(ZipError::from)?;
+                consumed.push((index, buf[0] as usize));
+                Ok(())
+            }).unwrap();
+
+        assert_eq!(consumed, jobs.iter().map(|job| (*job, *job)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stops_at_first_failed_job() {
+        let jobs: Vec<usize> = (0..100).collect();
+        let mut consumed = 0;
+
+        let result = CompressionPool::new(4).run(&jobs,
+            |job| if *job == 10 { Err(ZipError::new(ZipErrorKind::PasswordRequired)) } else { Ok(CompressedContent::Memory(vec![])) },
+            |_, _| {
+                consumed += 1;
+                Ok(())
+            });
+
+        assert!(result.is_err());
+        assert_eq!(consumed, 10);
+    }
+
+    #[test]
+    #[cfg(feature = "deflate")]
+    fn lends_permits_of_idle_threads() {
+        let thread_budget = ThreadBudget::new(4);
+        let permit = thread_budget.acquire();
+
+        let borrowed = thread_budget.try_acquire(8);
+        assert_eq!(borrowed.count(), 3);
+        assert_eq!(thread_budget.try_acquire(1).count(), 0);
+
+        drop(borrowed);
+        drop(permit);
+        assert_eq!(thread_budget.try_acquire(8).count(), 4);
+    }
+
+    #[test]
+    fn spills_large_content_to_file() {
+        let mut writer = CompressedContentWriter::new();
+        let data = vec![7u8; SPILL_THRESHOLD + 1];
+        writer.write_all(&data).unwrap();
+
+        let content = writer.finish().unwrap();
+        assert!(matches!(content, CompressedContent::Spilled(_)));
+
+        let mut buf = Vec::new();
+        content.copy_to(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+}
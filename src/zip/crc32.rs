@@ -1,6 +1,9 @@
-use std::{fs::File, io::{Read, Error}, path::PathBuf};
+use std::{fs::File, io::{Read, Error, Write}, path::PathBuf};
 use crc::{crc32, Hasher32};
 
+#[cfg(feature = "deflate")]
+const ZIP_CRC_POLYNOMIAL: u32 = 0xEDB88320;
+
 pub fn calculate_checksum(path: &PathBuf) -> Result<u32, Error> {
     let mut file = File::open(path)?;
     let mut buf = vec![0; 1_048_576];
@@ -16,4 +19,151 @@ pub fn calculate_checksum(path: &PathBuf) -> Result<u32, Error> {
     } {}
 
     Ok(digest.sum32())
+}
+
+// Writer discarding the written data, only keeping its checksum.
+pub struct ChecksumWriter {
+    digest: crc32::Digest
+}
+
+impl ChecksumWriter {
+    pub fn new() -> Self {
+        Self {
+            digest: crc32::Digest::new(crc32::IEEE)
+        }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.digest.sum32()
+    }
+}
+
+impl Write for ChecksumWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.digest.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Reader keeping the checksum and the size of the data read through it.
+pub struct ChecksumReader<R> {
+    reader: R,
+    digest: crc32::Digest,
+    read_size: u64
+}
+
+impl<R> ChecksumReader<R> where R: Read {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            digest: crc32::Digest::new(crc32::IEEE),
+            read_size: 0
+        }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.digest.sum32()
+    }
+
+    pub fn read_size(&self) -> u64 {
+        self.read_size
+    }
+}
+
+impl<R> Read for ChecksumReader<R> where R: Read {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let bytes_read = self.reader.read(buf)?;
+        self.digest.write(&buf[..bytes_read]);
+        self.read_size += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+// CRC32 of the concatenation of two blocks, given the checksums of both and the length of the second block.
+// Port of zlib's crc32_combine.
+#[cfg(feature = "deflate")]
+pub fn combine_checksums(first_checksum: u32, second_checksum: u32, second_len: u64) -> u32 {
+    if second_len == 0 {
+        return first_checksum;
+    }
+
+    let mut even = [0u32; 32];
+    let mut odd = [0u32; 32];
+
+    odd[0] = ZIP_CRC_POLYNOMIAL;
+    for (index, row) in odd.iter_mut().enumerate().skip(1) {
+        *row = 1 << (index - 1);
+    }
+
+    gf2_matrix_square(&mut even, &odd);
+    gf2_matrix_square(&mut odd, &even);
+
+    let mut checksum = first_checksum;
+    let mut remaining_len = second_len;
+
+    loop {
+        gf2_matrix_square(&mut even, &odd);
+        if remaining_len & 1 == 1 {
+            checksum = gf2_matrix_times(&even, checksum);
+        }
+        remaining_len >>= 1;
+        if remaining_len == 0 {
+            break;
+        }
+
+        gf2_matrix_square(&mut odd, &even);
+        if remaining_len & 1 == 1 {
+            checksum = gf2_matrix_times(&odd, checksum);
+        }
+        remaining_len >>= 1;
+        if remaining_len == 0 {
+            break;
+        }
+    }
+
+    checksum ^ second_checksum
+}
+
+#[cfg(feature = "deflate")]
+fn gf2_matrix_times(matrix: &[u32; 32], mut vector: u32) -> u32 {
+    let mut sum = 0;
+    let mut index = 0;
+
+    while vector != 0 {
+        if vector & 1 == 1 {
+            sum ^= matrix[index];
+        }
+        vector >>= 1;
+        index += 1;
+    }
+
+    sum
+}
+
+#[cfg(feature = "deflate")]
+fn gf2_matrix_square(square: &mut [u32; 32], matrix: &[u32; 32]) {
+    for (index, row) in square.iter_mut().enumerate() {
+        *row = gf2_matrix_times(matrix, matrix[index]);
+    }
+}
+
+#[cfg(all(test, feature = "deflate"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combined_checksum_equals_checksum_of_concatenation() {
+        let first = b"ruzito combines the checksums ";
+        let second = b"of independently compressed chunks.";
+        let concatenated = [&first[..], &second[..]].concat();
+
+        let combined = combine_checksums(crc32::checksum_ieee(first), crc32::checksum_ieee(second), second.len() as u64);
+
+        assert_eq!(combined, crc32::checksum_ieee(&concatenated));
+        assert_eq!(combine_checksums(crc32::checksum_ieee(first), 0, 0), crc32::checksum_ieee(first));
+    }
 }
\ No newline at end of file
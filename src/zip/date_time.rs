@@ -1,4 +1,6 @@
-use std::{fmt::Display, write};
+use std::{fmt::Display, io::{Error, ErrorKind}, time::SystemTime, write};
+
+use chrono::{DateTime, Datelike, Local, Timelike};
 
 const DAY_MASK: u16   = 0b11111;
 const MONTH_MASK: u16 = 0b1111;
@@ -48,6 +50,24 @@ impl ZipDateTime {
         }
     }
 
+    // The local date and time of the given system time.
+    pub fn from_system_time(system_time: SystemTime) -> Result<Self, Error> {
+        let system_time = SystemTime::UNIX_EPOCH + system_time.duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|err| Error::new(ErrorKind::Interrupted, err))?;
+
+        let datetime = DateTime::<Local>::from(system_time);
+
+        let day = datetime.date().day();
+        let month = datetime.date().month();
+        let year = datetime.date().year();
+
+        let hour = datetime.time().hour();
+        let minutes = datetime.time().minute();
+        let seconds = datetime.time().second();
+
+        Ok(ZipDateTime::new(day as u8, month as u8, year as u16, hour as u8, minutes as u8, seconds as u8))
+    }
+
     pub fn from_addr(date_addr: u16, time_addr: u16) -> Self {
         let day = (date_addr & DAY_MASK) as u8;
         let month = (date_addr >> 5 & MONTH_MASK) as u8;
@@ -67,7 +87,7 @@ impl ZipDateTime {
         }
     }
 
-    pub fn to_addr(self,  date_addr: &mut u16, time_addr: &mut u16) {
+    pub fn to_addr(&self,  date_addr: &mut u16, time_addr: &mut u16) {
         let month = (self.month << 5) as u16;
         let year = (self.year - MS_DOS_YEAR_START_OFFSET) << 9;
 
@@ -1 +1,2 @@
-pub mod zip_crypto;
\ No newline at end of file
+pub mod zip_crypto;
+pub mod winzip_aes;
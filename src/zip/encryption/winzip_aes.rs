@@ -0,0 +1,416 @@
+use std::io::{Error, ErrorKind, Read, Write};
+
+use aes::{Aes128, Aes192, Aes256, cipher::{BlockEncrypt, KeyInit, generic_array::GenericArray}};
+use byteorder::{ByteOrder, LittleEndian};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use zip::{error::{ZipError, ZipErrorKind}, mem_map::CompressionMethod};
+
+pub const AES_EXTRA_FIELD_ID: u16 = 0x9901;
+pub const AE_2_VENDOR_VERSION: u16 = 2;
+pub const AUTHENTICATION_CODE_SIZE: usize = 10;
+pub const PASSWORD_VERIFICATION_SIZE: usize = 2;
+
+const AES_VENDOR_ID: [u8; 2] = *b"AE";
+const AES_BLOCK_SIZE: usize = 16;
+const KEY_DERIVATION_ITERATIONS: u32 = 1000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AesStrength {
+    Aes128,
+    Aes192,
+    Aes256
+}
+
+impl AesStrength {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(AesStrength::Aes128),
+            2 => Some(AesStrength::Aes192),
+            3 => Some(AesStrength::Aes256),
+            _ => None
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            AesStrength::Aes128 => 1,
+            AesStrength::Aes192 => 2,
+            AesStrength::Aes256 => 3
+        }
+    }
+
+    pub fn key_size(self) -> usize {
+        match self {
+            AesStrength::Aes128 => 16,
+            AesStrength::Aes192 => 24,
+            AesStrength::Aes256 => 32
+        }
+    }
+
+    pub fn salt_size(self) -> usize {
+        self.key_size() / 2
+    }
+
+    // Bytes added to the content of an item by the encryption: the salt, the password verification value and the authentication code.
+    pub fn encryption_overhead(self) -> u64 {
+        (self.salt_size() + PASSWORD_VERIFICATION_SIZE + AUTHENTICATION_CODE_SIZE) as u64
+    }
+}
+
+// The 0x9901 extra field of WinZip AES encrypted items, holding the actual compression method of the item.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AesExtraField {
+    vendor_version: u16,
+    strength: AesStrength,
+    compression_method: CompressionMethod
+}
+
+impl AesExtraField {
+    pub fn new(vendor_version: u16, strength: AesStrength, compression_method: CompressionMethod) -> Self {
+        Self {
+            vendor_version,
+            strength,
+            compression_method
+        }
+    }
+
+    // Looks for the AES field among the fields of the given extra field.
+    pub fn from_extra_field(extra_field: &[u8]) -> Option<Self> {
+        let mut offset = 0;
+
+        while offset + 4 <= extra_field.len() {
+            let header_id = LittleEndian::read_u16(&extra_field[offset..offset + 2]);
+            let data_size = LittleEndian::read_u16(&extra_field[offset + 2..offset + 4]) as usize;
+            let data = extra_field.get(offset + 4..offset + 4 + data_size)?;
+
+            if header_id == AES_EXTRA_FIELD_ID && data.len() == 7 && data[2..4] == AES_VENDOR_ID {
+                return Some(Self {
+                    vendor_version: LittleEndian::read_u16(&data[0..2]),
+                    strength: AesStrength::from_byte(data[4])?,
+                    compression_method: CompressionMethod::from_addr(LittleEndian::read_u16(&data[5..7]))
+                });
+            }
+
+            offset += 4 + data_size;
+        }
+
+        None
+    }
+
+    pub fn vendor_version(&self) -> u16 {
+        self.vendor_version
+    }
+
+    pub fn strength(&self) -> AesStrength {
+        self.strength
+    }
+
+    pub fn compression_method(&self) -> CompressionMethod {
+        self.compression_method
+    }
+
+    // AE-2 items store no CRC32, their content is only protected by the authentication code.
+    pub fn has_crc32(&self) -> bool {
+        self.vendor_version != AE_2_VENDOR_VERSION
+    }
+
+    pub fn to_binary(self) -> Vec<u8> {
+        let mut aes_extra_field_bin = vec![0; 11];
+
+        LittleEndian::write_u16(&mut aes_extra_field_bin[0..2], AES_EXTRA_FIELD_ID);
+        LittleEndian::write_u16(&mut aes_extra_field_bin[2..4], 7);
+        LittleEndian::write_u16(&mut aes_extra_field_bin[4..6], self.vendor_version);
+        aes_extra_field_bin[6..8].copy_from_slice(&AES_VENDOR_ID);
+        aes_extra_field_bin[8] = self.strength.to_byte();
+        LittleEndian::write_u16(&mut aes_extra_field_bin[9..11], self.compression_method.to_addr());
+
+        aes_extra_field_bin
+    }
+}
+
+enum AesCipher {
+    Aes128(Aes128),
+    Aes192(Aes192),
+    Aes256(Aes256)
+}
+
+impl AesCipher {
+    fn new(strength: AesStrength, key: &[u8]) -> Self {
+        match strength {
+            AesStrength::Aes128 => AesCipher::Aes128(Aes128::new(GenericArray::from_slice(key))),
+            AesStrength::Aes192 => AesCipher::Aes192(Aes192::new(GenericArray::from_slice(key))),
+            AesStrength::Aes256 => AesCipher::Aes256(Aes256::new(GenericArray::from_slice(key)))
+        }
+    }
+
+    fn encrypt_block(&self, block: &mut [u8; AES_BLOCK_SIZE]) {
+        let block = GenericArray::from_mut_slice(block);
+
+        match self {
+            AesCipher::Aes128(cipher) => cipher.encrypt_block(block),
+            AesCipher::Aes192(cipher) => cipher.encrypt_block(block),
+            AesCipher::Aes256(cipher) => cipher.encrypt_block(block)
+        }
+    }
+}
+
+// AES in counter mode the way WinZip uses it, with a little endian counter starting from 1.
+struct AesCtr {
+    cipher: AesCipher,
+    counter: u128,
+    keystream: [u8; AES_BLOCK_SIZE],
+    keystream_offset: usize
+}
+
+impl AesCtr {
+    fn new(cipher: AesCipher) -> Self {
+        Self {
+            cipher,
+            counter: 0,
+            keystream: [0; AES_BLOCK_SIZE],
+            keystream_offset: AES_BLOCK_SIZE
+        }
+    }
+
+    fn apply_keystream(&mut self, buf: &mut [u8]) {
+        for byte in buf {
+            if self.keystream_offset == AES_BLOCK_SIZE {
+                self.counter = self.counter.wrapping_add(1);
+                self.keystream = self.counter.to_le_bytes();
+                self.cipher.encrypt_block(&mut self.keystream);
+                self.keystream_offset = 0;
+            }
+
+            *byte ^= self.keystream[self.keystream_offset];
+            self.keystream_offset += 1;
+        }
+    }
+}
+
+struct AesKeys {
+    encryption_key: Vec<u8>,
+    authentication_key: Vec<u8>,
+    password_verification: Vec<u8>
+}
+
+impl AesKeys {
+    fn derive(password: &str, salt: &[u8], strength: AesStrength) -> Self {
+        let key_size = strength.key_size();
+        let mut derived_key = vec![0; 2 * key_size + PASSWORD_VERIFICATION_SIZE];
+        pbkdf2::pbkdf2_hmac::<Sha1>(password.as_bytes(), salt, KEY_DERIVATION_ITERATIONS, &mut derived_key);
+
+        let password_verification = derived_key.split_off(2 * key_size);
+        let authentication_key = derived_key.split_off(key_size);
+
+        Self {
+            encryption_key: derived_key,
+            authentication_key,
+            password_verification
+        }
+    }
+}
+
+// Decrypts the content of a WinZip AES encrypted item and checks its authentication code once the content is read to the end.
+pub struct WinZipAesReader<R: Read> {
+    reader: R,
+    aes_ctr: AesCtr,
+    hmac: Hmac<Sha1>,
+    remaining_size: u64,
+    authenticated: bool
+}
+
+impl<R: Read> WinZipAesReader<R> {
+    // `encrypted_size` is the compressed size of the item, including the salt, the password verification value and the authentication code.
+    pub fn new(password: &str, strength: AesStrength, encrypted_size: u64, mut reader: R) -> Result<Self, ZipError> {
+        let overhead = strength.encryption_overhead();
+        if encrypted_size < overhead {
+            return Err(ZipError::with_cause(ZipErrorKind::InvalidArchive, "The AES encrypted item is too small."));
+        }
+
+        let mut salt = vec![0; strength.salt_size()];
+        let mut password_verification = vec![0; PASSWORD_VERIFICATION_SIZE];
+        reader.read_exact(&mut salt).map_err(ZipError::from)?;
+        reader.read_exact(&mut password_verification).map_err(ZipError::from)?;
+
+        let aes_keys = AesKeys::derive(password, &salt, strength);
+        if aes_keys.password_verification != password_verification {
+            return Err(ZipError::with_cause(ZipErrorKind::IncorrectPassword, "Password verification failed."));
+        }
+
+        Ok(Self {
+            reader,
+            aes_ctr: AesCtr::new(AesCipher::new(strength, &aes_keys.encryption_key)),
+            hmac: <Hmac<Sha1> as Mac>::new_from_slice(&aes_keys.authentication_key).expect("HMAC accepts keys of any size"),
+            remaining_size: encrypted_size - overhead,
+            authenticated: false
+        })
+    }
+
+    fn authenticate(&mut self) -> std::io::Result<()> {
+        let mut authentication_code = [0; AUTHENTICATION_CODE_SIZE];
+        self.reader.read_exact(&mut authentication_code)?;
+
+        let expected_code = self.hmac.clone().finalize().into_bytes();
+        if expected_code[..AUTHENTICATION_CODE_SIZE] != authentication_code {
+            return Err(ZipError::with_cause(ZipErrorKind::ChecksumMismatch, "AES authentication code does not match, the content is corrupted.").into());
+        }
+
+        self.authenticated = true;
+        Ok(())
+    }
+}
+
+// Encrypts the content of an item with a random salt. `finish` has to be called to write the authentication code.
+pub struct WinZipAesWriter<'a, W: Write> {
+    writer: &'a mut W,
+    aes_ctr: AesCtr,
+    hmac: Hmac<Sha1>
+}
+
+impl<'a, W: Write> WinZipAesWriter<'a, W> {
+    pub fn new(writer: &'a mut W, password: &str, strength: AesStrength) -> Result<Self, ZipError> {
+        let salt: Vec<u8> = (0..strength.salt_size())
+            .map(|_| rand::random())
+            .collect();
+        let aes_keys = AesKeys::derive(password, &salt, strength);
+
+        writer.write_all(&salt).map_err(ZipError::from)?;
+        writer.write_all(&aes_keys.password_verification).map_err(ZipError::from)?;
+
+        Ok(Self {
+            writer,
+            aes_ctr: AesCtr::new(AesCipher::new(strength, &aes_keys.encryption_key)),
+            hmac: <Hmac<Sha1> as Mac>::new_from_slice(&aes_keys.authentication_key).expect("HMAC accepts keys of any size")
+        })
+    }
+
+    pub fn finish(self) -> std::io::Result<()> {
+        let authentication_code = self.hmac.finalize().into_bytes();
+        self.writer.write_all(&authentication_code[..AUTHENTICATION_CODE_SIZE])
+    }
+}
+
+impl<'a, W: Write> Write for WinZipAesWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut encrypted_buf = buf.to_vec();
+        self.aes_ctr.apply_keystream(&mut encrypted_buf);
+        self.hmac.update(&encrypted_buf);
+        self.writer.write_all(&encrypted_buf)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<R: Read> Read for WinZipAesReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining_size == 0 {
+            if !self.authenticated {
+                self.authenticate()?;
+            }
+            return Ok(0);
+        }
+
+        let max_size = buf.len().min(self.remaining_size as usize);
+        let bytes_read = self.reader.read(&mut buf[..max_size])?;
+        if bytes_read == 0 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "The AES encrypted item ended unexpectedly."));
+        }
+
+        self.hmac.update(&buf[..bytes_read]);
+        self.aes_ctr.apply_keystream(&mut buf[..bytes_read]);
+        self.remaining_size -= bytes_read as u64;
+
+        Ok(bytes_read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // The encrypted content of "Hello WinZip AES-256" with the password "secret", taken from a ZIP file created by libarchive 3.8.2 with
+    // `bsdtar -c --format zip --options zip:encryption=aes256,zip:compression=store --passphrase secret -f hello.zip hello.txt`.
+    const ENCRYPTED_CONTENT: [u8; 48] = [0x9C, 0x0F, 0xB0, 0x18, 0xC3, 0xF6, 0x16, 0xAB, 0xBF, 0x86, 0x46, 0x6A, 0xF9, 0x93, 0x10, 0x4C, 0x18, 0x06, 0x5D, 0xA6,
+        0xE2, 0xB0, 0xC9, 0x81, 0x81, 0x10, 0xC5, 0x31, 0xC5, 0xFA, 0xDD, 0xD2, 0xB3, 0x49, 0x02, 0x3D, 0x16, 0xFB, 0x4F, 0x81, 0xD2, 0x95, 0x27, 0x14, 0xAC, 0x99, 0x6C, 0x19];
+
+    fn decrypt(password: &str, encrypted_content: &[u8]) -> Result<std::io::Result<Vec<u8>>, ZipError> {
+        let mut reader = WinZipAesReader::new(password, AesStrength::Aes256, encrypted_content.len() as u64, Cursor::new(encrypted_content))?;
+        let mut decrypted = Vec::new();
+        Ok(reader.read_to_end(&mut decrypted).map(|_| decrypted))
+    }
+
+    #[test]
+    fn decrypts_and_authenticates_content() {
+        let decrypted = decrypt("secret", &ENCRYPTED_CONTENT).unwrap().unwrap();
+
+        assert_eq!(decrypted, b"Hello WinZip AES-256");
+    }
+
+    #[test]
+    fn rejects_wrong_password_and_tampered_content() {
+        assert_eq!(decrypt("Secret", &ENCRYPTED_CONTENT).err().map(|err| err.kind()), Some(ZipErrorKind::IncorrectPassword));
+
+        let mut tampered_content = ENCRYPTED_CONTENT;
+        tampered_content[20] ^= 0x01;
+        let err = decrypt("secret", &tampered_content).unwrap().unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    // Created by libarchive 3.8.2 with `bsdtar -c --format zip --options zip:encryption=aes256,zip:compression=deflate --passphrase secret`,
+    // and aes128 for the second one.
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn reads_zip_files_encrypted_by_libarchive() {
+        use zip::ZipFile;
+
+        let aes256 = ZipFile::from_reader(Cursor::new(&include_bytes!("testdata/bsdtar_aes256.zip")[..])).unwrap();
+        let aes128 = ZipFile::from_reader(Cursor::new(&include_bytes!("testdata/bsdtar_aes128.zip")[..])).unwrap();
+        let notes = "Deflated before it is encrypted. ".repeat(40);
+
+        for (zip_file, item_path, expected_content) in [(&aes256, "hello.txt", "Hello WinZip AES-256"), (&aes256, "notes.txt", notes.as_str()), (&aes128, "notes.txt", notes.as_str())] {
+            let mut content = String::new();
+            zip_file.by_name_decrypt(item_path, "secret").unwrap().read_to_string(&mut content).unwrap();
+            assert_eq!(content, expected_content);
+        }
+        assert_eq!(aes128.by_name_decrypt("notes.txt", "Secret").err().map(|err| err.kind()), Some(ZipErrorKind::IncorrectPassword));
+    }
+
+    #[test]
+    fn encrypted_content_decrypts_back() {
+        let content = b"Encrypted with AES-128 and read back".repeat(10);
+        let mut encrypted_content = Vec::new();
+
+        let mut writer = WinZipAesWriter::new(&mut encrypted_content, "password", AesStrength::Aes128).unwrap();
+        writer.write_all(&content).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(encrypted_content.len() as u64, content.len() as u64 + AesStrength::Aes128.encryption_overhead());
+
+        let mut reader = WinZipAesReader::new("password", AesStrength::Aes128, encrypted_content.len() as u64, Cursor::new(encrypted_content)).unwrap();
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).unwrap();
+
+        assert_eq!(decrypted, content);
+    }
+
+    #[test]
+    fn finds_aes_field_among_extra_fields() {
+        let extra_field = [0x0A, 0x00, 0x02, 0x00, 0xFF, 0xFF, 0x01, 0x99, 0x07, 0x00, 0x02, 0x00, 0x41, 0x45, 0x03, 0x08, 0x00];
+        let aes_extra_field = AesExtraField::from_extra_field(&extra_field).unwrap();
+
+        assert_eq!(aes_extra_field.vendor_version(), 2);
+        assert_eq!(aes_extra_field.strength(), AesStrength::Aes256);
+        assert_eq!(aes_extra_field.compression_method(), CompressionMethod::Deflate);
+        assert!(!aes_extra_field.has_crc32());
+        assert_eq!(aes_extra_field.to_binary(), extra_field[6..]);
+        assert!(AesExtraField::from_extra_field(&extra_field[..6]).is_none());
+    }
+}
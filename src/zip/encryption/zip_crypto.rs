@@ -1,18 +1,16 @@
 use crc::crc32::make_table;
 use std::{io::Write, num::Wrapping};
-use std::io::{Read, Error};
+use std::io::Read;
 use byteorder::{ByteOrder, BigEndian};
 
+use zip::error::{ZipError, ZipErrorKind};
+
 const PKZIP_INITIAL_KEY_1: u32 = 0x12345678;
 const PKZIP_INITIAL_KEY_2: u32 = 0x23456789;
 const PKZIP_INITIAL_KEY_3: u32 = 0x34567890;
 const ZIP_CRYPTO_POLYNOMIAL: u32 = 0xEDB88320;
 
-#[derive(Debug)]
-pub enum ZipCryptoError {
-    InvalidPassword(String),
-    IOError(Error)
-}
+pub const ENCRYPTION_HEADER_SIZE: u64 = 12;
 
 struct ZipCrypto {
     key1: Wrapping<u32>,
@@ -78,24 +76,24 @@ pub struct ZipCryptoWriter<'a, W: Write> {
 }
 
 impl<'a, W: Write> ZipCryptoWriter<'a, W> {
-    pub fn new(writer: &'a mut W, password: &str, file_crc: u32) -> Result<Self, ZipCryptoError> {
+    pub fn new(writer: &'a mut W, password: &str, file_crc: u32) -> Result<Self, ZipError> {
         let mut crc_bytes = [0; 4];
         BigEndian::write_u32(&mut crc_bytes, file_crc);
        
         let mut encryption_key = Vec::with_capacity(12);
 
         // Generate encryption key
-        (0..11).into_iter()
+        (0..11)
             .for_each(|_| encryption_key.push(rand::random()));
         encryption_key.push(crc_bytes[0]);
 
         let mut self_obj = Self {
             zip_crypto: ZipCrypto::new(),
-            writer: writer
+            writer
         };
 
         // Initialize keys with the given password.
-        password.as_bytes().into_iter()
+        password.as_bytes().iter()
             .for_each(|ch| { self_obj.zip_crypto.update_keys(*ch) } );
 
         // Encrypt header
@@ -103,7 +101,7 @@ impl<'a, W: Write> ZipCryptoWriter<'a, W> {
             *key = self_obj.zip_crypto.encrypt_byte(*key);
         }
 
-        self_obj.writer.write_all(&encryption_key).map_err(|err| ZipCryptoError::IOError(err))?;
+        self_obj.writer.write_all(&encryption_key).map_err(ZipError::from)?;
 
         Ok(self_obj)
     }
@@ -112,7 +110,7 @@ impl<'a, W: Write> ZipCryptoWriter<'a, W> {
 
 impl<'a, W: Write> Write for ZipCryptoWriter<'a, W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let encrypted_buf: Vec<u8> = buf.into_iter()
+        let encrypted_buf: Vec<u8> = buf.iter()
             .map(|byte| self.zip_crypto.encrypt_byte(*byte))
             .collect();
         self.writer.write(&encrypted_buf)
@@ -125,7 +123,7 @@ impl<'a, W: Write> Write for ZipCryptoWriter<'a, W> {
 
 
 impl<R: Read> ZipCryptoReader<R> {
-    pub fn new(password: String, file_crc: u32, reader: R) -> Result<Self, ZipCryptoError> {
+    pub fn new(password: &str, file_crc: u32, reader: R) -> Result<Self, ZipError> {
 
         let mut self_obj = Self {
            zip_crypto: ZipCrypto::new(),
@@ -133,25 +131,23 @@ impl<R: Read> ZipCryptoReader<R> {
         };
 
         let mut encryption_header = vec![0; 12];
-        if let Err(err) = self_obj.reader.read_exact(&mut encryption_header) {
-            return Err(ZipCryptoError::IOError(err));
-        }
-        encryption_header = self_obj.decrypt_encryption_header(&password, &encryption_header);
+        self_obj.reader.read_exact(&mut encryption_header).map_err(ZipError::from)?;
+        encryption_header = self_obj.decrypt_encryption_header(password, &encryption_header);
 
         let mut crc_bytes = [0; 4];
         BigEndian::write_u32(&mut crc_bytes, file_crc);
         let crc_high_order_byte = &crc_bytes[0];
 
         if &encryption_header[11] != crc_high_order_byte {
-            return Err(ZipCryptoError::InvalidPassword(password))
+            return Err(ZipError::with_cause(ZipErrorKind::IncorrectPassword, "Password verification failed."))
         }
 
         Ok(self_obj)
     }
 
-    fn decrypt_encryption_header(&mut self, password: &String, encryption_header: &[u8]) -> Vec<u8> {
+    fn decrypt_encryption_header(&mut self, password: &str, encryption_header: &[u8]) -> Vec<u8> {
 
-        password.as_bytes().into_iter()
+        password.as_bytes().iter()
             .for_each(|ch| { self.zip_crypto.update_keys(*ch) } );
 
         encryption_header.iter()
@@ -165,11 +161,8 @@ impl<R: Read> ZipCryptoReader<R> {
 }
 
 impl<R: Read> Read for ZipCryptoReader<R> {
-    fn read(&mut self, mut buf: &mut [u8]) -> std::io::Result<usize> {
-        let read_buf_size = match self.reader.read(&mut buf) {
-            Ok(size) => size,
-            Err(err) => return Err(err)
-        };
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read_buf_size = self.reader.read(buf)?;
 
         buf.iter_mut().take(read_buf_size).for_each(|byte| { *byte = self.zip_crypto.decrypt_byte(*byte) });
 
@@ -185,7 +178,7 @@ mod tests {
     #[test]
     fn should_yield_error_if_password_is_wrong() {
         let cursor = Cursor::new([0xD0, 0x66, 0x78, 0x57, 0xA6, 0xC0, 0x45, 0x75, 0x7B, 0x0F, 0x77, 0x8F, 0x36, 0x53, 0x9b, 0x6f, 0xAC]);
-        let zip_crypto_encryption_reader = ZipCryptoReader::new(String::from("1234567"), 
+        let zip_crypto_encryption_reader = ZipCryptoReader::new("1234567", 
         0x2952CCF, 
         cursor);
 
@@ -194,7 +187,7 @@ mod tests {
     #[test]
     fn should_yield_zip_crypto_reader_if_password_is_correct() {
         let cursor = Cursor::new([0xD0, 0x66, 0x78, 0x57, 0xA6, 0xC0, 0x45, 0x75, 0x7B, 0x0F, 0x77, 0x8F, 0x36, 0x53, 0x9b, 0x6f, 0xAC]);
-        let mut zip_crypto_encryption_reader = ZipCryptoReader::new(String::from("123456"), 
+        let mut zip_crypto_encryption_reader = ZipCryptoReader::new("123456", 
         0x2952CCF, 
         cursor).unwrap();
 
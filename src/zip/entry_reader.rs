@@ -0,0 +1,172 @@
+use std::{cell::RefCell, io::Read, rc::Rc};
+
+use zip::{ZipError, ZipErrorKind, ZipFile, codec::{CodecRegistry, DecodeInfo}, crc32::ChecksumReader, zip_item::{ZipItem, unsupported_compression}};
+
+// Reads the decrypted and decompressed content of an item as a stream.
+// Once the content is read to the end, its size and CRC32 are checked and a mismatch is returned as an InvalidData error.
+pub struct ZipEntryReader<'a> {
+    decoder: ChecksumReader<Box<dyn Read + 'a>>,
+    content_reader: SharedReader<'a>,
+    crc32: Option<u32>,
+    uncompressed_size: u64,
+    verified: bool
+}
+
+impl<'a> ZipEntryReader<'a> {
+    // Layers the decoder of the item over the reader of its content, like `ZipItem::decompress_to` does.
+    pub(crate) fn new(item: &ZipItem, content_reader: Box<dyn Read + 'a>, general_purpose_flag: u16, codec_registry: &CodecRegistry) -> Result<Self, ZipError> {
+        let codec = codec_registry.codec(item.compression_method())
+            .ok_or_else(|| unsupported_compression(item.compression_method()))?;
+
+        let content_reader = SharedReader(Rc::new(RefCell::new(content_reader)));
+        let decode_info = DecodeInfo::new(general_purpose_flag, item.uncompressed_size() as u64);
+        let decoder = codec.decoder(Box::new(content_reader.clone()), &decode_info)
+            .map_err(ZipError::from)?;
+
+        Ok(Self {
+            decoder: ChecksumReader::new(decoder),
+            content_reader,
+            crc32: Some(item.crc32()).filter(|_| item.has_crc32()),
+            uncompressed_size: item.uncompressed_size() as u64,
+            verified: false
+        })
+    }
+
+    fn verify(&mut self) -> std::io::Result<()> {
+        // Codecs may stop before the end of the content, the authentication code of AES encrypted items is only checked once it is read.
+        std::io::copy(&mut self.content_reader, &mut std::io::sink())?;
+
+        if self.decoder.read_size() != self.uncompressed_size {
+            return Err(ZipError::with_cause(ZipErrorKind::ChecksumMismatch, "The size of the content does not match the size of the item.").into());
+        }
+        if self.crc32.is_some_and(|crc32| crc32 != self.decoder.checksum()) {
+            return Err(ZipError::with_cause(ZipErrorKind::ChecksumMismatch, "CRC32 checksum does not match.").into());
+        }
+
+        self.verified = true;
+        Ok(())
+    }
+}
+
+impl<'a> Read for ZipEntryReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let bytes_read = self.decoder.read(buf)?;
+
+        if bytes_read == 0 && !buf.is_empty() && !self.verified {
+            self.verify()?;
+        }
+
+        Ok(bytes_read)
+    }
+}
+
+// The content reader is shared by the decoder and the entry reader, which reads what the decoder leaves behind.
+#[derive(Clone)]
+struct SharedReader<'a>(Rc<RefCell<Box<dyn Read + 'a>>>);
+
+impl<'a> Read for SharedReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+impl ZipFile {
+    pub fn by_name(&self, item_path: &str) -> Result<ZipEntryReader<'_>, ZipError> {
+        self.by_index(self.existing_index_of(item_path)?)
+    }
+
+    pub fn by_index(&self, index: usize) -> Result<ZipEntryReader<'_>, ZipError> {
+        self.entry_reader(index, None)
+    }
+
+    pub fn by_name_decrypt(&self, item_path: &str, password: &str) -> Result<ZipEntryReader<'_>, ZipError> {
+        self.by_index_decrypt(self.existing_index_of(item_path)?, password)
+    }
+
+    pub fn by_index_decrypt(&self, index: usize, password: &str) -> Result<ZipEntryReader<'_>, ZipError> {
+        self.entry_reader(index, Some(password))
+    }
+
+    fn existing_index_of(&self, item_path: &str) -> Result<usize, ZipError> {
+        self.index_of(item_path)
+            .ok_or_else(|| ZipError::new(ZipErrorKind::ItemNotFound).at_path(item_path))
+    }
+
+    // ZIP files read with `from_reader` cannot be read again, they fail with ReaderInUse, until the returned reader is dropped.
+    fn entry_reader(&self, index: usize, password: Option<&str>) -> Result<ZipEntryReader<'_>, ZipError> {
+        let item = self.zip_items.get(index)
+            .ok_or_else(|| ZipError::with_cause(ZipErrorKind::ItemNotFound, format!("There is no item #{}.", index)))?;
+        item.check_supported(&self.codec_registry)
+            .map_err(|err| err.at_item(item))?;
+
+        let zip_file_reader = self.source.reader()?;
+        item.content_reader(password, zip_file_reader)
+            .and_then(|(content_reader, general_purpose_flag)| ZipEntryReader::new(item, content_reader, general_purpose_flag, &self.codec_registry))
+            .map_err(|err| err.at_item(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+    use zip::{ZipError, ZipErrorKind, ZipFile, mem_map::CompressionMethod, options::{AesStrength, EntryOptions, Password, ZipEncryption}, writer::ZipWriter};
+
+    fn zip_file(options: &EntryOptions, content: &[u8]) -> Vec<u8> {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.start_file("content.txt", options).unwrap()
+            .write_all(content).unwrap();
+        zip_writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn reads_entries_as_streams() {
+        let content = b"Streamed out of the ZIP file ".repeat(100);
+        let mut encrypted = EntryOptions::default();
+        encrypted.set_encryption(ZipEncryption::WinZipAes(AesStrength::Aes256), Password::new(String::from("secret")));
+
+        let zip_file = ZipFile::from_reader(Cursor::new(zip_file(&encrypted, &content))).unwrap();
+        let mut read_content = Vec::new();
+        zip_file.by_name_decrypt("content.txt", "secret").unwrap().read_to_end(&mut read_content).unwrap();
+        assert_eq!(read_content, content);
+
+        assert_eq!(zip_file.by_name("missing.txt").err().map(|err| err.kind()), Some(ZipErrorKind::ItemNotFound));
+        let password_required = zip_file.by_index(0).err().unwrap();
+        assert_eq!(password_required.kind(), ZipErrorKind::PasswordRequired);
+        assert_eq!(password_required.item_path(), Some("content.txt"));
+    }
+
+    #[test]
+    fn corrupted_content_fails_checksum() {
+        let mut archive = zip_file(&EntryOptions::new(CompressionMethod::NoCompression, None), b"Hello ruzito");
+        // The content starts after the 30 bytes of the local file header and the 11 bytes of the item path.
+        archive[41] ^= 0xFF;
+
+        let zip_file = ZipFile::from_reader(Cursor::new(archive)).unwrap();
+        let result = zip_file.by_index(0).unwrap().read_to_end(&mut Vec::new());
+
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        let mut entry_reader = zip_file.by_index(0).unwrap();
+        assert_eq!(ZipError::from(entry_reader.read_to_end(&mut Vec::new()).unwrap_err()).kind(), ZipErrorKind::ChecksumMismatch);
+    }
+
+    #[test]
+    fn reads_one_entry_at_a_time_from_a_reader() {
+        let options = EntryOptions::new(CompressionMethod::Deflate, None);
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.start_file("first.txt", &options).unwrap().write_all(b"First").unwrap();
+        zip_writer.start_file("second.txt", &options).unwrap().write_all(b"Second").unwrap();
+        let zip_file = ZipFile::from_reader(Cursor::new(zip_writer.finish().unwrap().into_inner())).unwrap();
+
+        let mut first = zip_file.by_name("first.txt").unwrap();
+        assert_eq!(zip_file.by_name("second.txt").err().map(|err| err.kind()), Some(ZipErrorKind::ReaderInUse));
+        assert_eq!(zip_file.by_index(0).err().map(|err| err.kind()), Some(ZipErrorKind::ReaderInUse));
+        let mut first_content = String::new();
+        first.read_to_string(&mut first_content).unwrap();
+        drop(first);
+
+        let mut second_content = String::new();
+        zip_file.by_name("second.txt").unwrap().read_to_string(&mut second_content).unwrap();
+        assert_eq!(first_content, "First");
+        assert_eq!(second_content, "Second");
+    }
+}
@@ -1,9 +1,10 @@
 use super::mem_map::END_OF_CENTRAL_DIR_SIGNATURE;
 use byteorder::{LittleEndian, ByteOrder};
-use std::{io::{Error, ErrorKind}, str};
+use std::io::{Error, ErrorKind};
 
 pub const MIN_EOF_CENTRAL_DIRECTORY_SIZE: usize = 22;
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct EndOfCentralDirectory {
     signature: u32,
@@ -22,6 +23,10 @@ impl EndOfCentralDirectory {
         self.cdfh_start_offset
     }
 
+    pub fn size_of_central_dir(&self) -> u32 {
+        self.size_of_central_dir
+    }
+
     pub fn total_num_of_central_dir(&self) -> u16 {
         self.total_num_of_central_dir
     }
@@ -38,7 +43,9 @@ impl EndOfCentralDirectory {
         }
 
         let zip_comment_len = LittleEndian::read_u16(&eof_bin[20..22]);
-        let zip_comment_end_offset = MIN_EOF_CENTRAL_DIRECTORY_SIZE as u16 + zip_comment_len;
+        let zip_comment_end_offset = MIN_EOF_CENTRAL_DIRECTORY_SIZE + zip_comment_len as usize;
+        let zip_comment = eof_bin.get(MIN_EOF_CENTRAL_DIRECTORY_SIZE..zip_comment_end_offset)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "The ZIP file comment is truncated."))?;
 
         Ok(EndOfCentralDirectory {
             signature: END_OF_CENTRAL_DIR_SIGNATURE,
@@ -49,7 +56,7 @@ impl EndOfCentralDirectory {
             size_of_central_dir: LittleEndian::read_u32(&eof_bin[12..16]),
             cdfh_start_offset: LittleEndian::read_u32(&eof_bin[16..20]),
             zip_comment_len: LittleEndian::read_u16(&eof_bin[20..22]),
-            zip_comment: String::from(str::from_utf8(&eof_bin[22..zip_comment_end_offset as usize]).unwrap())
+            zip_comment: String::from_utf8_lossy(zip_comment).into_owned()
         })
     }
 
@@ -67,7 +74,7 @@ impl EndOfCentralDirectory {
         }
     }
 
-    pub fn to_binary(self) -> Vec<u8> {
+    pub fn to_binary(&self) -> Vec<u8> {
         let mut eof_bin = Vec::with_capacity(MIN_EOF_CENTRAL_DIRECTORY_SIZE);
 
         let mut signature_bytes = vec![0, 0, 0, 0];
@@ -121,7 +128,7 @@ mod tests {
 
         let eof_central_dir = EndOfCentralDirectory::from(bin.as_ref()).unwrap();
 
-        assert_eq!(eof_central_dir.signature, END_OF_CENTRAL_DIR_SIGNATURE as u32);
+        assert_eq!(eof_central_dir.signature, END_OF_CENTRAL_DIR_SIGNATURE);
         assert_eq!(eof_central_dir.num_of_disk, 0);
         assert_eq!(eof_central_dir.num_of_disk_start_central_dir, 0);
         assert_eq!(eof_central_dir.num_of_central_dir, 9);
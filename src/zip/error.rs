@@ -0,0 +1,198 @@
+use std::{error::Error, fmt::{self, Display, Formatter}};
+
+use zip::zip_item::ZipItem;
+
+// What went wrong, callers can match on it without parsing messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipErrorKind {
+    // Reading or writing a file failed.
+    Io,
+    // The ZIP file or one of its headers is malformed.
+    InvalidArchive,
+    // The content of an item does not match its CRC32 or authentication code.
+    ChecksumMismatch,
+    IncorrectPassword,
+    // The item is encrypted, or is to be encrypted, and no password is given.
+    PasswordRequired,
+    UnsupportedCompression,
+    UnsupportedEncryption,
+    // The path of an item points outside of the destination folder.
+    UnsafePath,
+    // A path to zip or extract to is invalid.
+    InvalidPath,
+    ItemNotFound,
+    // The job was cancelled through its CancellationToken.
+    Cancelled,
+    // The ZIP file was read with `from_reader` and its reader is held by an entry reader that is still alive.
+    ReaderInUse,
+    // An item or the ZIP file is too large for the 32 bit sizes and offsets, or has too many items for the 16 bit count, ZIP64 is not supported.
+    Zip64Required
+}
+
+impl ZipErrorKind {
+    fn description(self) -> &'static str {
+        match self {
+            ZipErrorKind::Io => "I/O error",
+            ZipErrorKind::InvalidArchive => "invalid ZIP file",
+            ZipErrorKind::ChecksumMismatch => "checksum does not match",
+            ZipErrorKind::IncorrectPassword => "incorrect password",
+            ZipErrorKind::PasswordRequired => "password required",
+            ZipErrorKind::UnsupportedCompression => "unsupported compression method",
+            ZipErrorKind::UnsupportedEncryption => "unsupported encryption",
+            ZipErrorKind::UnsafePath => "item path points outside of the destination",
+            ZipErrorKind::InvalidPath => "invalid path",
+            ZipErrorKind::ItemNotFound => "item not found",
+            ZipErrorKind::Cancelled => "cancelled",
+            ZipErrorKind::ReaderInUse => "ZIP file reader in use",
+            ZipErrorKind::Zip64Required => "ZIP64 required"
+        }
+    }
+}
+
+impl Display for ZipErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+// The error of every operation of ruzito. It carries the item and the offset of the item in the ZIP file when
+// the error belongs to one, and the underlying cause, e.g. an I/O error.
+#[derive(Debug)]
+pub struct ZipError {
+    kind: ZipErrorKind,
+    item_path: Option<String>,
+    offset: Option<u64>,
+    cause: Option<Box<dyn Error + Send + Sync>>
+}
+
+impl ZipError {
+    pub fn new(kind: ZipErrorKind) -> Self {
+        Self {
+            kind,
+            item_path: None,
+            offset: None,
+            cause: None
+        }
+    }
+
+    // The cause can be another error or a message.
+    pub fn with_cause<E>(kind: ZipErrorKind, cause: E) -> Self
+    where E: Into<Box<dyn Error + Send + Sync>> {
+        Self {
+            cause: Some(cause.into()),
+            ..ZipError::new(kind)
+        }
+    }
+
+    pub fn kind(&self) -> ZipErrorKind {
+        self.kind
+    }
+
+    pub fn item_path(&self) -> Option<&str> {
+        self.item_path.as_deref()
+    }
+
+    // Offset of the local file header of the item in the ZIP file.
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    // Attributes the error to the given item, unless it already belongs to one.
+    pub(crate) fn at_item(mut self, item: &ZipItem) -> Self {
+        if self.item_path.is_none() {
+            self.item_path = Some(item.item_path().clone());
+            self.offset = Some(item.start_offset() as u64);
+        }
+        self
+    }
+
+    // Offset of a header in the ZIP file that could not be read.
+    pub(crate) fn at_offset(mut self, offset: u64) -> Self {
+        self.offset = self.offset.or(Some(offset));
+        self
+    }
+
+    pub(crate) fn at_path(mut self, item_path: &str) -> Self {
+        if self.item_path.is_none() {
+            self.item_path = Some(String::from(item_path));
+        }
+        self
+    }
+}
+
+impl Display for ZipError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.cause {
+            Some(cause) => write!(f, "{}: {}", self.kind, cause)?,
+            None => write!(f, "{}", self.kind)?
+        }
+
+        match (&self.item_path, self.offset) {
+            (Some(item_path), Some(offset)) => write!(f, " ({} at offset {})", item_path, offset),
+            (Some(item_path), None) => write!(f, " ({})", item_path),
+            _ => Ok(())
+        }
+    }
+}
+
+impl Error for ZipError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.cause.as_ref().map(|cause| cause.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+// Errors raised below a Read or Write are carried through std::io::Error, they are unwrapped again here.
+// Malformed data is reported as an invalid ZIP file.
+impl From<std::io::Error> for ZipError {
+    fn from(err: std::io::Error) -> Self {
+        match err.downcast::<ZipError>() {
+            Ok(zip_error) => zip_error,
+            Err(err) => {
+                let kind = match err.kind() {
+                    std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => ZipErrorKind::InvalidArchive,
+                    _ => ZipErrorKind::Io
+                };
+                ZipError::with_cause(kind, err)
+            }
+        }
+    }
+}
+
+impl From<ZipError> for std::io::Error {
+    fn from(err: ZipError) -> Self {
+        let kind = match err.kind {
+            // Not Interrupted, reads and writes are retried on it.
+            ZipErrorKind::Io | ZipErrorKind::Cancelled | ZipErrorKind::ReaderInUse => std::io::ErrorKind::Other,
+            ZipErrorKind::ItemNotFound => std::io::ErrorKind::NotFound,
+            ZipErrorKind::InvalidPath | ZipErrorKind::PasswordRequired => std::io::ErrorKind::InvalidInput,
+            _ => std::io::ErrorKind::InvalidData
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::{date_time::ZipDateTime, mem_map::{CompressionMethod, EncryptionMethod}};
+
+    #[test]
+    fn describes_item_and_cause() {
+        let item = ZipItem::new(CompressionMethod::Deflate, String::from("docs/readme.txt"), 0, 0, ZipDateTime::new(1, 1, 2021, 0, 0, 0), 1234, EncryptionMethod::NoEncryption, 0);
+        let err = ZipError::with_cause(ZipErrorKind::ChecksumMismatch, "CRC32 is 0x00000000").at_item(&item);
+
+        assert_eq!(err.to_string(), "checksum does not match: CRC32 is 0x00000000 (docs/readme.txt at offset 1234)");
+        assert_eq!(err.item_path(), Some("docs/readme.txt"));
+        assert_eq!(err.offset(), Some(1234));
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn keeps_kind_through_io_errors() {
+        let io_error: std::io::Error = ZipError::new(ZipErrorKind::IncorrectPassword).into();
+        assert_eq!(ZipError::from(io_error).kind(), ZipErrorKind::IncorrectPassword);
+
+        let invalid_data = std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid signature.");
+        assert_eq!(ZipError::from(invalid_data).kind(), ZipErrorKind::InvalidArchive);
+    }
+}
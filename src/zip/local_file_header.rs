@@ -1,9 +1,10 @@
-use std::{io::{Error, ErrorKind, SeekFrom}};
+use std::io::{Error, ErrorKind};
 use std::io::prelude::*;
 use byteorder::{LittleEndian, ByteOrder};
-use super::{mem_map::{ZipVersion, CompressionMethod, FILE_HEADER_SIGNATURE, EncryptionMethod, MINIMUM_SIZE_TO_COMPRESS}, zip_item::ZipItem};
+use super::{mem_map::{ZipVersion, CompressionMethod, FILE_HEADER_SIGNATURE, EncryptionMethod}, zip_item::ZipItem};
 use super::date_time::ZipDateTime;
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct LocalFileHeader {
     signature: u32,
@@ -25,7 +26,7 @@ pub struct LocalFileHeader {
 impl LocalFileHeader {
     pub fn from_reader<R>(reader: &mut R) -> Result<Self, Error>
     where R: Read + Seek {
-        let start_offset = reader.seek(SeekFrom::Current(0)).unwrap();
+        let start_offset = reader.stream_position()?;
 
         let mut cdf_bytes = vec![0; 30];
         reader.read_exact(&mut cdf_bytes)?;
@@ -47,14 +48,7 @@ impl LocalFileHeader {
         let compressed_size = LittleEndian::read_u32(&cdf_bytes[18..22]);
         let uncompressed_size = LittleEndian::read_u32(&cdf_bytes[22..26]);
         let compression_method = CompressionMethod::from_addr(LittleEndian::read_u16(&cdf_bytes[8..10]));
-        let encryption_method = if general_purpose_flag & 0b100001 == 0b100001 {
-                EncryptionMethod::StrongEncryption
-            }
-            else if general_purpose_flag & 0x1 == 1 && compression_method != CompressionMethod::Aex {
-                EncryptionMethod::ZipCrypto
-            }
-            else if compression_method == CompressionMethod::Aex { EncryptionMethod::WinZipAesEncryption }
-            else { EncryptionMethod::NoEncryption };
+        let encryption_method = EncryptionMethod::from_header(general_purpose_flag, compression_method);
 
             if general_purpose_flag >> 2 & 1 == 1 {
                 return Err(Error::new(ErrorKind::InvalidData, "Data descriptor is currently not supported."));
@@ -77,7 +71,8 @@ impl LocalFileHeader {
             compressed_size,
             uncompressed_size,
             file_name_length,
-            file_name: String::from_utf8(file_name_bytes).unwrap(),
+            file_name: String::from_utf8(file_name_bytes)
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "The file name is not valid UTF-8."))?,
             extra_field_length,
             extra_field,
             content_start_offset
@@ -85,20 +80,22 @@ impl LocalFileHeader {
     }
 
     pub fn from_zip_item(zip_item: &ZipItem) -> Self {
+            let extra_field = zip_item.extra_field();
+
             LocalFileHeader {
                 signature: FILE_HEADER_SIGNATURE,
-                version_needed_to_extract: ZipVersion::new(2, 0),
-                general_purpose_flag: if zip_item.encryption_method() == EncryptionMethod::ZipCrypto { 0x01 } else { 0x00 },
-                compression_method: zip_item.compression_method(),
+                version_needed_to_extract: zip_item.version_needed_to_extract(),
+                general_purpose_flag: zip_item.general_purpose_flag(),
+                compression_method: zip_item.header_compression_method(),
                 encryption_method: zip_item.encryption_method(),
                 last_modified_date_time: zip_item.modified_date_time().to_owned(),
-                crc32: zip_item.crc32(),
+                crc32: zip_item.header_crc32(),
                 compressed_size: zip_item.compressed_size(),
                 uncompressed_size: zip_item.uncompressed_size(),
                 file_name_length: zip_item.item_path().len() as u16,
                 file_name: zip_item.item_path().to_owned(),
-                extra_field_length: 0,
-                extra_field: vec![],
+                extra_field_length: extra_field.len() as u16,
+                extra_field,
                 content_start_offset: 0
             }
     }
@@ -107,6 +104,14 @@ impl LocalFileHeader {
         self.compression_method
     }
 
+    pub fn general_purpose_flag(&self) -> u16 {
+        self.general_purpose_flag
+    }
+
+    pub fn extra_field(&self) -> &[u8] {
+        &self.extra_field
+    }
+
     pub fn content_start_offset(&self) -> u64 {
         self.content_start_offset
     }
@@ -116,23 +121,15 @@ impl LocalFileHeader {
     }
 
     pub fn crc32(&self) -> u32 {
-        return self.crc32
+        self.crc32
     }
 
-    pub fn to_binary(self) -> Vec<u8> {
+    pub fn to_binary(&self) -> Vec<u8> {
         let mut local_file_header_bin = Vec::with_capacity(30);
         let mut signature = vec![0x50u8, 0x4b, 0x03, 0x04];
-        let mut version_needed_to_extract = vec![0x14u8, 0x00];
-        let mut general_purpose_bit_flag = if self.encryption_method != EncryptionMethod::NoEncryption {
-            vec![0x01u8, 0x00]
-        } else {
-            vec![0x00, 0x00]
-        };
-        let mut compression_method = if self.uncompressed_size < MINIMUM_SIZE_TO_COMPRESS {
-            vec![0x00, 0x00]
-        } else {
-            vec![0x08u8, 0x00]
-        };
+        let mut version_needed_to_extract = vec![self.version_needed_to_extract.to_byte(), 0x00];
+        let mut general_purpose_bit_flag = vec![0, 0];
+        let mut compression_method = vec![0, 0];
         let mut last_modification_time = 0;
         let mut last_modification_day = 0;
 
@@ -148,6 +145,8 @@ impl LocalFileHeader {
 
         LittleEndian::write_u16(&mut last_modification_day_bytes, last_modification_day);
         LittleEndian::write_u16(&mut last_modification_time_bytes, last_modification_time);
+        LittleEndian::write_u16(&mut general_purpose_bit_flag, self.general_purpose_flag);
+        LittleEndian::write_u16(&mut compression_method, self.compression_method.to_addr());
         LittleEndian::write_u32(&mut crc32, self.crc32);
         LittleEndian::write_u32(&mut compressed_size, self.compressed_size);
         LittleEndian::write_u32(&mut uncompressed_size, self.uncompressed_size);
@@ -166,7 +165,7 @@ impl LocalFileHeader {
         local_file_header_bin.append(&mut file_name_length);
         local_file_header_bin.append(&mut extra_field_length);
         local_file_header_bin.append(&mut Vec::from(self.file_name.as_bytes()));
-        local_file_header_bin.append(&mut Vec::from(self.extra_field));
+        local_file_header_bin.extend_from_slice(&self.extra_field);
 
         local_file_header_bin
     }
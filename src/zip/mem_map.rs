@@ -2,8 +2,14 @@ pub const FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
 pub const END_OF_CENTRAL_DIR_SIGNATURE: u32 = 0x06054b50;
 pub const CENTRAL_DIR_SIGNATURE: u32 = 0x02014b50;
 
-pub const MINIMUM_SIZE_TO_COMPRESS: u32 = 10_000;
+pub const ENCRYPTED_FLAG: u16 = 0x01;
+// General purpose flag bit telling that the LZMA stream is terminated by an end of stream marker.
+pub const LZMA_EOS_MARKER_FLAG: u16 = 0x02;
+// Bits 1 and 2 of the general purpose flag, their meaning depends on the compression method, e.g. the dictionary size of imploded items.
+pub const COMPRESSION_FLAGS_MASK: u16 = 0x06;
+pub const STRONG_ENCRYPTION_FLAG: u16 = 0x40;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Eq, Debug)]
 pub enum HostOS {
     MsDos,
@@ -58,16 +64,34 @@ impl HostOS {
         }
     }
 
-    pub fn from_os() -> Self {
-        match std::env::consts::OS {
-            "windows" => HostOS::MsDos,
-            "linux" => HostOS::Unix,
-            "macos" => HostOS::MACINTOSH,
-            _ => HostOS::UNUSED
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            HostOS::MsDos => 0,
+            HostOS::Amiga => 1,
+            HostOS::OpenVms => 2,
+            HostOS::Unix => 3,
+            HostOS::VmCms => 4,
+            HostOS::AtariIst => 5,
+            HostOS::OS2 => 6,
+            HostOS::MACINTOSH => 7,
+            HostOS::ZSystem => 8,
+            HostOS::CPM => 9,
+            HostOS::WinNTFS => 10,
+            HostOS::MVS => 11,
+            HostOS::VSE => 12,
+            HostOS::RISC => 13,
+            HostOS::VFAT => 14,
+            HostOS::AlternativeMVS => 15,
+            HostOS::BEOS => 16,
+            HostOS::TANDEM => 17,
+            HostOS::OS400 => 18,
+            HostOS::OSX => 19,
+            HostOS::UNUSED => 255
         }
     }
 
 
+
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -78,7 +102,28 @@ pub enum EncryptionMethod {
     StrongEncryption
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl EncryptionMethod {
+    // Reads the encryption method from the general purpose flag and the compression method of a header.
+    pub fn from_header(general_purpose_flag: u16, compression_method: CompressionMethod) -> Self {
+        if general_purpose_flag & ENCRYPTED_FLAG == 0 {
+            EncryptionMethod::NoEncryption
+        }
+        else if general_purpose_flag & STRONG_ENCRYPTION_FLAG != 0 {
+            EncryptionMethod::StrongEncryption
+        }
+        else if compression_method == CompressionMethod::Aex {
+            EncryptionMethod::WinZipAesEncryption
+        }
+        else {
+            EncryptionMethod::ZipCrypto
+        }
+    }
+}
+
+// The version of the ZIP specification ruzito implements, written as "version made by".
+pub const ZIP_SPECIFICATION_VERSION: ZipVersion = ZipVersion { major: 6, minor: 3 };
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct ZipVersion {
     major: u8,
     minor: u8
@@ -102,8 +147,13 @@ impl ZipVersion {
             minor
         }
     }
+
+    pub fn to_byte(self) -> u8 {
+        self.major * 10 + self.minor
+    }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum CompressionMethod {
     NoCompression,
@@ -117,7 +167,7 @@ pub enum CompressionMethod {
     Deflate,
     Deflate64,
     PKWAREDataCompressionLib,
-    Reserved,
+    Reserved(u16),
     BZIP2,
     LZMA,
     ZOSCMPSC,
@@ -131,7 +181,7 @@ pub enum CompressionMethod {
     WavPack,
     PPMd,
     Aex,
-    Unknown
+    Unknown(u16)
 }
 
 impl CompressionMethod {
@@ -148,13 +198,13 @@ impl CompressionMethod {
             8 => CompressionMethod::Deflate,
             9 => CompressionMethod::Deflate64,
             10 => CompressionMethod::PKWAREDataCompressionLib,
-            11 => CompressionMethod::Reserved,
+            11 => CompressionMethod::Reserved(11),
             12 => CompressionMethod::BZIP2,
-            13 => CompressionMethod::Reserved,
+            13 => CompressionMethod::Reserved(13),
             14 => CompressionMethod::LZMA,
-            15 => CompressionMethod::Reserved,
+            15 => CompressionMethod::Reserved(15),
             16 => CompressionMethod::ZOSCMPSC,
-            17 => CompressionMethod::Reserved,
+            17 => CompressionMethod::Reserved(17),
             18 => CompressionMethod::IBMTerse,
             19 => CompressionMethod::IBMLZ77,
             20 => CompressionMethod::Deprecated,
@@ -165,7 +215,72 @@ impl CompressionMethod {
             97 => CompressionMethod::WavPack,
             98 => CompressionMethod::PPMd,
             99 => CompressionMethod::Aex,
-            _ => CompressionMethod::Unknown
+            _ => CompressionMethod::Unknown(addr)
+        }
+    }
+
+    pub fn to_addr(self) -> u16 {
+        match self {
+            CompressionMethod::NoCompression => 0,
+            CompressionMethod::Shrunk => 1,
+            CompressionMethod::Factor1 => 2,
+            CompressionMethod::Factor2 => 3,
+            CompressionMethod::Factor3 => 4,
+            CompressionMethod::Factor4 => 5,
+            CompressionMethod::Implode => 6,
+            CompressionMethod::ReservedTokenCompression => 7,
+            CompressionMethod::Deflate => 8,
+            CompressionMethod::Deflate64 => 9,
+            CompressionMethod::PKWAREDataCompressionLib => 10,
+            CompressionMethod::BZIP2 => 12,
+            CompressionMethod::LZMA => 14,
+            CompressionMethod::ZOSCMPSC => 16,
+            CompressionMethod::IBMTerse => 18,
+            CompressionMethod::IBMLZ77 => 19,
+            CompressionMethod::Deprecated => 20,
+            CompressionMethod::ZStandard => 93,
+            CompressionMethod::MP3 => 94,
+            CompressionMethod::XZ => 95,
+            CompressionMethod::JPEGVariant => 96,
+            CompressionMethod::WavPack => 97,
+            CompressionMethod::PPMd => 98,
+            CompressionMethod::Aex => 99,
+            CompressionMethod::Reserved(addr) | CompressionMethod::Unknown(addr) => addr
         }
     }
+
+    pub fn version_needed_to_extract(self) -> ZipVersion {
+        match self {
+            CompressionMethod::Deflate64 => ZipVersion::new(2, 1),
+            CompressionMethod::BZIP2 => ZipVersion::new(4, 6),
+            CompressionMethod::LZMA | CompressionMethod::XZ | CompressionMethod::ZStandard => ZipVersion::new(6, 3),
+            _ => ZipVersion::new(2, 0)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_method_addr_round_trips() {
+        for addr in 0..=100 {
+            assert_eq!(CompressionMethod::from_addr(addr).to_addr(), addr);
+        }
+    }
+
+    #[test]
+    fn encryption_method_is_read_from_header() {
+        assert_eq!(EncryptionMethod::from_header(0x00, CompressionMethod::Deflate), EncryptionMethod::NoEncryption);
+        assert_eq!(EncryptionMethod::from_header(0x01, CompressionMethod::Deflate), EncryptionMethod::ZipCrypto);
+        assert_eq!(EncryptionMethod::from_header(0x01, CompressionMethod::Aex), EncryptionMethod::WinZipAesEncryption);
+        assert_eq!(EncryptionMethod::from_header(0x41, CompressionMethod::Deflate), EncryptionMethod::StrongEncryption);
+        assert_eq!(EncryptionMethod::from_header(0x21, CompressionMethod::Deflate), EncryptionMethod::ZipCrypto);
+    }
+
+    #[test]
+    fn bzip2_needs_version_4_6() {
+        assert_eq!(CompressionMethod::BZIP2.version_needed_to_extract().to_byte(), 46);
+        assert_eq!(CompressionMethod::Deflate.version_needed_to_extract().to_byte(), 20);
+    }
 }
\ No newline at end of file
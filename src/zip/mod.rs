@@ -1,55 +1,57 @@
-use std::{ffi::OsString, fs::{File, OpenOptions}, io::{BufReader, BufWriter, Seek, SeekFrom, Write}, path::{Path, PathBuf}, process::exit};
+use std::{collections::HashMap, convert::TryFrom, ffi::OsString, fs::File, io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}, sync::{Mutex, OnceLock, atomic::{AtomicBool, AtomicUsize, Ordering}}, thread};
 
 
-use self::{encryption::{zip_crypto::ZipCryptoError, zip_crypto::ZipCryptoWriter}, local_file_header::LocalFileHeader, central_dir_file_header::CentralDirectoryFileHeader, eof_central_dir::EndOfCentralDirectory, mem_map::EncryptionMethod, options::{ExtractOptions, ZipOptions}, zip_item::ZipItem};
+use self::{encryption::{winzip_aes::WinZipAesWriter, zip_crypto::ZipCryptoWriter}, local_file_header::LocalFileHeader, central_dir_file_header::CentralDirectoryFileHeader, eof_central_dir::EndOfCentralDirectory, mem_map::{CompressionMethod, EncryptionMethod}, options::{ExtractOptions, Password, PasswordPrompt, ZipEncryption, ZipOptions}, zip_item::{ZipItem, unsupported_compression}};
 
 
 mod local_file_header;
 mod eof_central_dir;
 mod central_dir_file_header;
-mod zip_metadata;
-mod date_time;
-mod compression_decoder;
-mod compression_encoder;
-mod encryption;
+pub(crate) mod zip_metadata;
+pub(crate) mod date_time;
+pub(crate) mod encryption;
 mod crc32;
-
+mod compression_pool;
+pub(crate) mod cancellation;
+pub(crate) mod error;
+pub(crate) mod entry_reader;
+mod password;
+pub(crate) mod progress;
+mod recrypt;
+mod source;
+pub(crate) mod writer;
+
+pub mod codec;
 pub mod options;
 pub mod mem_map;
 pub mod zip_item;
 pub mod zip_item_creator;
 
-use zip::crc32::calculate_checksum;
+pub use self::error::{ZipError, ZipErrorKind};
 
-#[derive(Debug)]
-pub enum ZipError {
-    FileIOError(std::io::Error),
-    ZipCryptoError(ZipCryptoError),
-    PasswordDoesNotExist
-}
-
-#[derive(Debug)]
-pub enum ExtractError {
-    InvalidParentPath(String),
-    CreateDirError(String),
-    FileCreationFailed,
-    UnableToSeekZipItem(u32),
-    IOError(std::io::Error),
-    ZipCryptoError(ZipCryptoError),
-}
+use zip::crc32::{ChecksumWriter, calculate_checksum};
+use zip::codec::CodecRegistry;
+#[cfg(feature = "deflate")]
+use zip::codec::deflate::{self, MIN_SIZE_TO_DEFLATE_IN_PARALLEL};
+use zip::compression_pool::{CompressedContent, CompressedContentWriter, CompressionPool, ThreadBudget};
+use zip::cancellation::{CancellableReader, check_cancelled};
+use zip::password::PasswordResolver;
+use zip::progress::{ProgressObserver, ProgressReader};
+use zip::source::ZipSource;
+use zip::zip_metadata::CentralDirectoryIter;
 
-#[derive(Debug)]
-pub enum ZipCreatorError {
-    InvalidPath(OsString),
-    IOError(std::io::Error),
-}
+// Result of testing one file of a ZIP file.
+pub type ItemTestResult<'a> = (&'a ZipItem, Result<(), ZipError>);
 
 #[derive(Debug)]
 pub struct ZipFile {
     file_count: u16,
     zip_items: Vec<zip_item::ZipItem>,
-    zip_file_path: OsString,
-    file_encryption_method: EncryptionMethod
+    source: ZipSource,
+    file_encryption_method: EncryptionMethod,
+    codec_registry: CodecRegistry,
+    // Index of every item by its path, built the first time an item is looked up by path.
+    name_index: OnceLock<HashMap<String, usize>>
 }
 
 pub struct ZipFileIntoIterator<'a> {
@@ -62,23 +64,32 @@ impl<'a> ZipFileIntoIterator<'a> {
     pub fn new(zip_files: &'a Vec<zip_item::ZipItem>) -> Self {
         Self {
             index: 0,
-            zip_files: zip_files
+            zip_files
         }
     }
 }
 impl ZipFile {
     pub fn new<P>(zip_file_path: P) -> Result<Self, ZipError>
     where P: AsRef<Path> {
-        let file_path_os_string = OsString::from(zip_file_path.as_ref().as_os_str());
+        ZipFile::open(ZipSource::Path(OsString::from(zip_file_path.as_ref().as_os_str())))
+    }
 
-        let (eof_central_dir, file_headers) = zip_metadata::ZipMetadata::parse(zip_file_path)
-            .map_err(|io_error| ZipError::FileIOError(io_error))?;
-        
-        let zip_items : Vec<ZipItem> = file_headers.into_iter()
-            .map(|item| item.into())
-            .collect();
+    // Reads the ZIP file from any source, e.g. a Cursor over an archive in memory.
+    // Items of such ZIP files are extracted one at a time, whatever the number of threads is.
+    pub fn from_reader<R>(reader: R) -> Result<Self, ZipError>
+    where R: Read + Seek + Send + 'static {
+        ZipFile::open(ZipSource::Reader(Mutex::new(Box::new(reader))))
+    }
+
+    fn open(source: ZipSource) -> Result<Self, ZipError> {
+        let (file_count, zip_items) = {
+            let mut zip_file_reader = source.reader()?;
+            let central_directory = CentralDirectoryIter::from_reader(&mut zip_file_reader)?;
+            let file_count = central_directory.file_count();
+            (file_count, central_directory.collect::<Result<Vec<ZipItem>, ZipError>>()?)
+        };
 
-        let file_encryption_method = if zip_items.len() > 0 {
+        let file_encryption_method = if !zip_items.is_empty() {
             if let Some(zip_item) = zip_items.iter().find(|zip_item| zip_item.encryption_method() != EncryptionMethod::NoEncryption) {
                 zip_item.encryption_method()
             }
@@ -91,9 +102,11 @@ impl ZipFile {
 
         Ok(ZipFile {
             zip_items, 
-            file_count: eof_central_dir.total_num_of_central_dir(),
-            zip_file_path: file_path_os_string,
-            file_encryption_method
+            file_count,
+            source,
+            file_encryption_method,
+            codec_registry: CodecRegistry::default(),
+            name_index: OnceLock::new()
         })
     }
 
@@ -101,216 +114,316 @@ impl ZipFile {
         Self {
                file_count,
                zip_items,
-               zip_file_path,
-               file_encryption_method
+               source: ZipSource::Path(zip_file_path),
+               file_encryption_method,
+               codec_registry: CodecRegistry::default(),
+               name_index: OnceLock::new()
+        }
+    }
+
+    // Replaces the codecs used to compress and decompress the items, e.g. to add a codec for a compression method ruzito does not know.
+    pub fn set_codec_registry(&mut self, codec_registry: CodecRegistry) {
+        self.codec_registry = codec_registry;
+    }
+
+    pub fn codec_registry(&self) -> &CodecRegistry {
+        &self.codec_registry
+    }
+
+    // Extracts every item into the destination folder. Nothing is extracted if an item can not be extracted with the registered codecs.
+    // The first error stops the extraction, with several threads the error of the first failing item in the archive is returned.
+    pub fn extract_all(&mut self, options: ExtractOptions) -> Result<(), ZipError> {
+        let mut zip_file_reader = self.source.reader()?;
+
+        for item in &self.zip_items {
+            item.check_supported(&self.codec_registry)
+                .map_err(|err| err.at_item(item))?;
+        }
+
+        let password_resolver = PasswordResolver::new(options.passwords().to_vec(), options.password_prompt());
+
+        if options.threads() > 1 && self.source.is_reopenable() {
+            return self.extract_all_in_parallel(&options, &password_resolver, &mut zip_file_reader);
+        }
+
+        for item in &self.zip_items {
+            ZipFile::extract_item(item, &options, &password_resolver, &self.codec_registry, &mut zip_file_reader)
+                .map_err(|err| err.at_item(item))?;
         }
+
+        Ok(())
     }
 
-    pub fn extract_all(&mut self, options: ExtractOptions) {
-        let mut item_iterator = self.zip_items.iter_mut();
-
-        while let Some(item) = item_iterator.next() {
-            let item_extract_result = item.extract(&options);
-            let is_file = item.is_file();
-
-            match item_extract_result {
-                Ok(path) => {
-                    let output_file_path = PathBuf::new().join(path.as_ref());
-                    
-                    if is_file {
-                        match calculate_checksum(&output_file_path) {
-                            Ok(checksum) => {
-                                if checksum != item.crc32() {
-                                    eprintln!("CRC32 checksum do not match! Exiting...");
-                                    exit(-1);
-                                }
-                            },
-                            Err(err) => {
-                                eprintln!("I/O error occured while calculating the checksum! {}", err);
-                                exit(-1);
-                            }
+    // Directories are created up front, then files are extracted by worker threads each reading the ZIP file through its own reader.
+    // Once an entry fails no new entries are started.
+    fn extract_all_in_parallel<R>(&self, options: &ExtractOptions, password_resolver: &PasswordResolver, zip_file_reader: &mut R) -> Result<(), ZipError>
+    where R: Read + Seek {
+        for item in self.zip_items.iter().filter(|item| !item.is_file()) {
+            ZipFile::extract_item(item, options, password_resolver, &self.codec_registry, zip_file_reader)
+                .map_err(|err| err.at_item(item))?;
+        }
+
+        let files: Vec<(usize, &ZipItem)> = self.zip_items.iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_file())
+            .collect();
+        let next_file = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let errors = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for _ in 0..options.threads() {
+                scope.spawn(|| {
+                    let mut zip_file_reader = match self.source.reader() {
+                        Ok(zip_file_reader) => zip_file_reader,
+                        Err(err) => {
+                            failed.store(true, Ordering::SeqCst);
+                            errors.lock().unwrap().push((0, err));
+                            return;
                         }
-                    }
-                },
-                Err(err) => {
-                println!("An error occured while extracting the file {}!", item.item_path());
-                match err {
-                        ExtractError::InvalidParentPath(parent_path) => eprintln!("Invalid parent path to extract files! Given Path: {}", parent_path),
-                        ExtractError::CreateDirError(dir_path) => eprintln!("Unable to create directory of {}", dir_path),
-                        ExtractError::FileCreationFailed => eprintln!("Unable to create the extracted file!"),
-                        ExtractError::UnableToSeekZipItem(offset) => eprintln!("Unable to seek the ZIP file!, Failed offset: {}", offset),
-                        ExtractError::IOError(err) => eprintln!("I/O error occured while extracting the file! {}", err),
-                        ExtractError::ZipCryptoError(err) => {
-                            match err {
-                                ZipCryptoError::InvalidPassword(_) => { 
-                                    eprintln!("Incorrect Password. Exiting.."); 
-                                    exit(-1);
-                                },
-                                ZipCryptoError::IOError(err) => eprintln!("I/O error occured while decrypting the file! {}", err)
-                            }
+                    };
+
+                    while !failed.load(Ordering::SeqCst) {
+                        let (item_index, item) = match files.get(next_file.fetch_add(1, Ordering::SeqCst)) {
+                            Some(file) => *file,
+                            None => break
+                        };
+
+                        if let Err(err) = ZipFile::extract_item(item, options, password_resolver, &self.codec_registry, &mut zip_file_reader) {
+                            failed.store(true, Ordering::SeqCst);
+                            errors.lock().unwrap().push((item_index, err.at_item(item)));
                         }
                     }
-
-                    break;
-                }
+                });
             }
+        });
 
+        match errors.into_inner().unwrap().into_iter().min_by_key(|(item_index, _)| *item_index) {
+            Some((_, err)) => Err(err),
+            None => Ok(())
         }
     }
 
-    pub fn create_zip_file(&mut self, zip_options: &ZipOptions) -> Result<(), ZipError> {
-
-        if zip_options.dest_path().exists() && zip_options.dest_path().is_file() {
-            std::fs::remove_file(zip_options.dest_path()).map_err(|err| ZipError::FileIOError(err))?;
+    fn extract_item<R>(item: &ZipItem, options: &ExtractOptions, password_resolver: &PasswordResolver, codec_registry: &CodecRegistry, zip_file_reader: &mut R) -> Result<(), ZipError>
+    where R: Read + Seek {
+        check_cancelled(options.cancellation_token())?;
+        if let Some(progress_observer) = options.progress_observer() {
+            progress_observer.entry_started(item.item_path(), item.uncompressed_size() as u64);
         }
 
-        let dest_path_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(zip_options.dest_path()).map_err(|err| ZipError::FileIOError(err))?;
+        let zip_password = password_resolver.resolve(item, zip_file_reader)?;
+        let path = item.extract(options, zip_password.as_ref().map(Password::as_str), codec_registry, zip_file_reader)?;
 
-        let mut file_writer = BufWriter::new(dest_path_file);
-        let mut cdfh_vec = Vec::with_capacity(self.zip_items.len());
+        if item.is_file() && item.has_crc32() {
+            let output_file_path = PathBuf::new().join(path.as_ref());
+            let checksum = calculate_checksum(&output_file_path)
+                .map_err(ZipError::from)?;
 
-        for zip_item in &mut self.zip_items {
-            if zip_options.verbose_mode() {
-                println!("{}", zip_item.item_path());
+            if checksum != item.crc32() {
+                return Err(ZipError::with_cause(ZipErrorKind::ChecksumMismatch, format!("CRC32 is {:#010x}, {:#010x} is expected.", checksum, item.crc32())));
             }
+        }
 
-            let zip_item_start_offset = file_writer.seek(SeekFrom::End(0))
-                       .map_err(|err| ZipError::FileIOError(err))?;
+        if let Some(progress_observer) = options.progress_observer() {
+            progress_observer.entry_finished(item.item_path());
+        }
 
+        Ok(())
+    }
 
-            zip_item.update_start_offset(zip_item_start_offset as u32);
+    // Decompresses every file in the archive without writing it anywhere and checks its CRC32.
+    // Returns the result of every file, passwords are resolved per item like when extracting.
+    pub fn test_all(&self, passwords: Vec<Password>, password_prompt: Option<&(dyn PasswordPrompt + Sync)>) -> Result<Vec<ItemTestResult<'_>>, ZipError> {
+        let mut zip_file_reader = self.source.reader()?;
+        let password_resolver = PasswordResolver::new(passwords, password_prompt);
+
+        let test_results = self.zip_items.iter()
+            .filter(|item| item.is_file())
+            .map(|item| {
+                let result = item.check_supported(&self.codec_registry)
+                    .and_then(|_| password_resolver.resolve(item, &mut zip_file_reader))
+                    .and_then(|zip_password| {
+                        let mut checksum_writer = ChecksumWriter::new();
+                        item.decompress_to(zip_password.as_ref().map(Password::as_str), &self.codec_registry, &mut zip_file_reader, &mut checksum_writer)?;
+
+                        if item.has_crc32() && checksum_writer.checksum() != item.crc32() {
+                            return Err(ZipError::with_cause(ZipErrorKind::ChecksumMismatch, format!("CRC32 is {:#010x}, {:#010x} is expected.", checksum_writer.checksum(), item.crc32())));
+                        }
+                        Ok(())
+                    })
+                    .map_err(|err| err.at_item(item));
 
-            let reader = match ZipFile::generate_file_reader(zip_item, zip_options) {
-                Ok(reader) => reader,
-                Err(err) => {
-                    return Err(err);
-                }
-            };
+                (item, result)
+            })
+            .collect();
 
-            if zip_item.is_file() {
-                // Unwrap is safe here. We make sure that there'll always be a reader for each file.
-                let mut buf_reader = reader.unwrap();
+        Ok(test_results)
+    }
 
-                let local_file_header = LocalFileHeader::from_zip_item(zip_item);
-                let file_crc32 = local_file_header.crc32();
+    // The ZIP file is written to a temporary file next to the destination, which replaces the destination once it is complete.
+    // A failed or cancelled zipping leaves the destination as it was.
+    pub fn create_zip_file(&mut self, zip_options: &ZipOptions) -> Result<(), ZipError> {
+        let dest_dir = match zip_options.dest_path().parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new(".")
+        };
+        let temp_file = temp_file_builder().tempfile_in(dest_dir).map_err(ZipError::from)?;
 
-                //Write local file header
-                file_writer.write_all(&local_file_header.to_binary())
-                    .map_err(|err| ZipError::FileIOError(err))?;
+        let mut file_writer = BufWriter::new(temp_file.as_file());
+        let mut cdfh_vec = Vec::with_capacity(self.zip_items.len());
 
-                let file_start_offset = file_writer.seek(SeekFrom::Current(0))
-                                .map_err(|err| ZipError::FileIOError(err))?;    
+        let compression_jobs: Vec<CompressionJob> = self.zip_items.iter()
+            .map(|zip_item| CompressionJob::new(zip_item, zip_options))
+            .collect();
+        let zip_items = &mut self.zip_items;
+        let codec_registry = &self.codec_registry;
 
-                if zip_options.encrypt_file() {
-                    let password = match zip_options.password() {
-                        Some(password) => password,
-                        None => return Err(ZipError::PasswordDoesNotExist)
-                    };
+        if zip_options.threads() == 1 {
+            // A single thread compresses every item straight into the ZIP file.
+            for (zip_item, compression_job) in zip_items.iter_mut().zip(&compression_jobs) {
+                compression_job.start(zip_options)?;
 
-                    let mut zip_crypto_writer = match ZipCryptoWriter::new(&mut file_writer, 
-                        password, 
-                        file_crc32) {
-                        Ok(writer) => writer,
-                        Err(err) => return Err(ZipError::ZipCryptoError(err))
-                    };
+                let cdfh = ZipFile::write_zip_item(&mut file_writer, zip_item, |writer, zip_item| {
+                    compression_job.compress_into(writer, zip_item, codec_registry, zip_options)
+                })?;
+                cdfh_vec.push(cdfh);
 
-                    compression_encoder::CompressionEncoder::encode_to_file(
-                                    &zip_item.compression_method(),
-                                    &mut buf_reader,
-                             &mut zip_crypto_writer)
-                                     .map_err(|err| ZipError::FileIOError(err)
-                                    )?;
-                }
-                else {
-                    compression_encoder::CompressionEncoder::encode_to_file(
-                                    &zip_item.compression_method(),
-                                    &mut buf_reader,
-                             &mut file_writer)
-                                     .map_err(|err| ZipError::FileIOError(err)
-                                    )?;
+                if let Some(progress_observer) = zip_options.progress_observer() {
+                    progress_observer.entry_finished(zip_item.item_path());
                 }
-
-                let file_end_offset = file_writer.seek(SeekFrom::Current(0))
-                                .map_err(|err| ZipError::FileIOError(err))?;    
-                            
-                let file_compressed_size = (file_end_offset - file_start_offset) as u32;
-                zip_item.update_compressed_size(file_compressed_size);
-                
             }
+        } else {
+            let compression_pool = CompressionPool::new(zip_options.threads());
+            compression_pool.run(&compression_jobs,
+                |compression_job| compression_job.compress(codec_registry, zip_options, compression_pool.thread_budget()),
+                |item_index, (compressed_content, compression_method)| {
+                    check_cancelled(zip_options.cancellation_token())?;
+                    let zip_item = &mut zip_items[item_index];
+                    zip_item.update_compression_method(compression_method);
+
+                    let cdfh = ZipFile::write_zip_item(&mut file_writer, zip_item, |writer, _| {
+                        compressed_content.copy_to(writer)
+                            .map(|_| ())
+                            .map_err(ZipError::from)
+                    })?;
+                    cdfh_vec.push(cdfh);
+
+                    if let Some(progress_observer) = zip_options.progress_observer() {
+                        progress_observer.entry_finished(zip_item.item_path());
+                    }
+                    Ok(())
+                })?;
+        }
 
-            file_writer.seek(SeekFrom::Start(zip_item.start_offset() as u64))
-                .map_err(|err| ZipError::FileIOError(err))?;
-
-            //Update local file header with updated compressed size
-            file_writer.write_all(&LocalFileHeader::from_zip_item(zip_item).to_binary())
-                .map_err(|err| ZipError::FileIOError(err))?;
+        ZipFile::write_central_directory(&mut file_writer, cdfh_vec)?;
+        file_writer.flush().map_err(ZipError::from)?;
+        drop(file_writer);
 
-            file_writer.seek(SeekFrom::End(0))
-                .map_err(|err| ZipError::FileIOError(err))?;
-            
-            cdfh_vec.push(CentralDirectoryFileHeader::from_zip_item(zip_item));
-        }
+        temp_file.persist(zip_options.dest_path())
+            .map(|_| ())
+            .map_err(|err| ZipError::from(err.error))
+    }
 
+    fn write_central_directory<W>(file_writer: &mut W, cdfh_vec: Vec<CentralDirectoryFileHeader>) -> Result<(), ZipError>
+    where W: Write + Seek {
+        let item_count = without_zip64(cdfh_vec.len(), "The number of items")?;
         let mut cdfh_size = 0;
-        let cdfh_start_offset = file_writer.seek(SeekFrom::End(0))
-            .map_err(|err| ZipError::FileIOError(err))? as u32;
+        let cdfh_start_offset = without_zip64(file_writer.seek(SeekFrom::End(0)).map_err(ZipError::from)?, "The offset of the central directory")?;
 
         for cdfh in cdfh_vec {
             let cdfh_bin = cdfh.to_binary();
-            cdfh_size = cdfh_size + cdfh_bin.len();
-            file_writer.write_all(&&cdfh_bin)
-                .map_err(|err| ZipError::FileIOError(err))?;
+            cdfh_size += cdfh_bin.len();
+            file_writer.write_all(&cdfh_bin)
+                .map_err(ZipError::from)?;
         }
-                
-        let item_count = self.zip_items.len() as u16;
-        let eocd = EndOfCentralDirectory::from_zip_creator(item_count, cdfh_size as u32, cdfh_start_offset);
-        let mut  eocd_bytes = eocd.to_binary(); 
 
-        file_writer.write_all(&mut eocd_bytes).map_err(|err| ZipError::FileIOError(err))?;
+        let eocd = EndOfCentralDirectory::from_zip_creator(item_count, without_zip64(cdfh_size, "The size of the central directory")?, cdfh_start_offset);
+        let eocd_bytes = eocd.to_binary(); 
+
+        file_writer.write_all(&eocd_bytes).map_err(ZipError::from)?;
 
         Ok(())
     }
 
-    fn generate_file_reader(zip_item: &mut ZipItem, zip_options: &ZipOptions) -> Result<Option<BufReader<File>>, ZipError> {
+    fn write_zip_item<W, F>(file_writer: &mut W, zip_item: &mut ZipItem, write_content: F) -> Result<CentralDirectoryFileHeader, ZipError>
+    where W: Write + Seek,
+          F: FnOnce(&mut W, &mut ZipItem) -> Result<(), ZipError> {
 
-        let file_path_on_disk = if zip_options.base_path().is_dir() {
-            PathBuf::new().join(zip_options.base_path()).join(zip_item.item_path())
-        } else {
-            zip_options.base_path().clone()
-        };
+        let zip_item_start_offset = file_writer.seek(SeekFrom::End(0))
+            .map_err(ZipError::from)?;
 
-        let zip_item_reader;
+        zip_item.update_start_offset(without_zip64(zip_item_start_offset, "The offset of the item")?);
 
         if zip_item.is_file() {
-            let file_to_zip = File::open(file_path_on_disk)
-                .map_err(|err| ZipError::FileIOError(err))?;
-
-            let buf_reader = BufReader::new(file_to_zip);
-            zip_item_reader = Some(buf_reader);
-        } 
-        else {
-            zip_item_reader = None;
+            //Write local file header
+            file_writer.write_all(&LocalFileHeader::from_zip_item(zip_item).to_binary())
+                .map_err(ZipError::from)?;
+
+            let file_start_offset = file_writer.stream_position()
+                .map_err(ZipError::from)?;
+
+            write_content(file_writer, zip_item)?;
+
+            let file_end_offset = file_writer.stream_position()
+                .map_err(ZipError::from)?;
+
+            let file_compressed_size = without_zip64(file_end_offset - file_start_offset, "The compressed size")
+                .map_err(|err| err.at_item(zip_item))?;
+            zip_item.update_compressed_size(file_compressed_size);
         }
 
-        Ok(zip_item_reader)
+        file_writer.seek(SeekFrom::Start(zip_item.start_offset() as u64))
+            .map_err(ZipError::from)?;
+
+        //Update local file header with updated compressed size
+        file_writer.write_all(&LocalFileHeader::from_zip_item(zip_item).to_binary())
+            .map_err(ZipError::from)?;
+
+        file_writer.seek(SeekFrom::End(0))
+            .map_err(ZipError::from)?;
+
+        Ok(CentralDirectoryFileHeader::from_zip_item(zip_item))
+    }
+
+    fn file_path_on_disk(zip_item: &ZipItem, zip_options: &ZipOptions) -> PathBuf {
+        if zip_options.base_path().is_dir() {
+            PathBuf::new().join(zip_options.base_path()).join(zip_item.item_path())
+        } else {
+            zip_options.base_path().clone()
+        }
     }
 
     pub fn file_count(&self) -> u16 {
         self.file_count
     }
 
-    pub fn zip_file_path(&self) -> &OsString {
-        &self.zip_file_path
+    // None for ZIP files read with `from_reader`.
+    pub fn zip_file_path(&self) -> Option<&OsString> {
+        self.source.path()
     }
 
     pub fn file_encryption_method(&self) -> &EncryptionMethod {
         &self.file_encryption_method
     }
 
+    pub fn index_of(&self, item_path: &str) -> Option<usize> {
+        self.name_index
+            .get_or_init(|| {
+                let mut name_index = HashMap::with_capacity(self.zip_items.len());
+                for (index, item) in self.zip_items.iter().enumerate() {
+                    // Paths may appear twice in an archive, the first item is the one found.
+                    name_index.entry(item.item_path().clone()).or_insert(index);
+                }
+                name_index
+            })
+            .get(item_path)
+            .copied()
+    }
+
+    pub fn item(&self, item_path: &str) -> Option<&ZipItem> {
+        self.index_of(item_path).map(|index| &self.zip_items[index])
+    }
+
     pub fn iter<'a>(&'a self) -> ZipFileIntoIterator<'a> {
         ZipFileIntoIterator::new(&self.zip_items)
     }
@@ -318,6 +431,152 @@ impl ZipFile {
    
 }
 
+struct CompressionJob {
+    item_path: String,
+    file_path_on_disk: Option<PathBuf>,
+    compression_method: CompressionMethod,
+    store_if_not_smaller: bool,
+    uncompressed_size: u32,
+    crc32: u32,
+    encryption: Option<ZipEncryption>
+}
+
+impl CompressionJob {
+    fn new(zip_item: &ZipItem, zip_options: &ZipOptions) -> Self {
+        Self {
+            item_path: zip_item.item_path().clone(),
+            file_path_on_disk: Some(ZipFile::file_path_on_disk(zip_item, zip_options)).filter(|_| zip_item.is_file()),
+            compression_method: zip_item.compression_method(),
+            store_if_not_smaller: zip_options.compression_options().compression_method().is_none(),
+            uncompressed_size: zip_item.uncompressed_size(),
+            crc32: zip_item.crc32(),
+            encryption: match zip_item.encryption_method() {
+                EncryptionMethod::ZipCrypto => Some(ZipEncryption::ZipCrypto),
+                EncryptionMethod::WinZipAesEncryption => zip_item.aes_extra_field().map(|aes_extra_field| ZipEncryption::WinZipAes(aes_extra_field.strength())),
+                _ => None
+            }
+        }
+    }
+
+    fn start(&self, zip_options: &ZipOptions) -> Result<(), ZipError> {
+        check_cancelled(zip_options.cancellation_token())?;
+        if let Some(progress_observer) = zip_options.progress_observer() {
+            progress_observer.entry_started(&self.item_path, self.uncompressed_size as u64);
+        }
+
+        Ok(())
+    }
+
+    // Returns the compressed content together with the compression method it is compressed with, used by the workers of the pool.
+    fn compress(&self, codec_registry: &CodecRegistry, zip_options: &ZipOptions, thread_budget: &ThreadBudget) -> Result<(CompressedContent, CompressionMethod), ZipError> {
+        self.start(zip_options)?;
+
+        let mut content_writer = CompressedContentWriter::new();
+        self.compress_with(&self.compression_method, codec_registry, zip_options, Some(thread_budget), zip_options.progress_observer(), &mut content_writer)?;
+        let compressed_content = content_writer.finish().map_err(ZipError::from)?;
+
+        if self.should_store(compressed_content.len().map_err(ZipError::from)?) {
+            // The bytes of the file are already reported.
+            let mut content_writer = CompressedContentWriter::new();
+            self.compress_with(&CompressionMethod::NoCompression, codec_registry, zip_options, None, None, &mut content_writer)?;
+            return Ok((content_writer.finish().map_err(ZipError::from)?, CompressionMethod::NoCompression));
+        }
+
+        Ok((compressed_content, self.compression_method))
+    }
+
+    // Compresses the content straight into the ZIP file at its current position, used when zipping with a single thread.
+    // Content that does not get smaller is written again stored, over the compressed content.
+    fn compress_into(&self, file_writer: &mut BufWriter<&File>, zip_item: &mut ZipItem, codec_registry: &CodecRegistry, zip_options: &ZipOptions) -> Result<(), ZipError> {
+        let content_start_offset = file_writer.stream_position().map_err(ZipError::from)?;
+        self.compress_with(&self.compression_method, codec_registry, zip_options, None, zip_options.progress_observer(), file_writer)?;
+        let compressed_size = file_writer.stream_position().map_err(ZipError::from)? - content_start_offset;
+
+        if self.should_store(compressed_size) {
+            file_writer.seek(SeekFrom::Start(content_start_offset)).map_err(ZipError::from)?;
+            self.compress_with(&CompressionMethod::NoCompression, codec_registry, zip_options, None, None, file_writer)?;
+
+            let content_end_offset = file_writer.stream_position().map_err(ZipError::from)?;
+            file_writer.flush().map_err(ZipError::from)?;
+            file_writer.get_ref().set_len(content_end_offset).map_err(ZipError::from)?;
+            zip_item.update_compression_method(CompressionMethod::NoCompression);
+        }
+
+        Ok(())
+    }
+
+    // Unless a compression method is chosen explicitly, content that does not get smaller is stored instead.
+    fn should_store(&self, compressed_size: u64) -> bool {
+        let encryption_overhead = self.encryption.map_or(0, ZipEncryption::encryption_overhead);
+
+        self.store_if_not_smaller && self.compression_method != CompressionMethod::NoCompression
+            && compressed_size >= self.uncompressed_size as u64 + encryption_overhead
+    }
+
+    fn compress_with<W>(&self, compression_method: &CompressionMethod, codec_registry: &CodecRegistry, zip_options: &ZipOptions, thread_budget: Option<&ThreadBudget>, progress_observer: Option<&dyn ProgressObserver>, content_writer: &mut W) -> Result<(), ZipError>
+    where W: Write {
+        if let Some(file_path_on_disk) = &self.file_path_on_disk {
+            let file_to_zip = File::open(file_path_on_disk).map_err(ZipError::from)?;
+            let file_reader = CancellableReader::new(file_to_zip, zip_options.cancellation_token());
+            let mut buf_reader = BufReader::new(ProgressReader::new(file_reader, progress_observer));
+
+            match self.encryption {
+                Some(encryption) => {
+                    let password = match zip_options.password() {
+                        Some(password) => password,
+                        None => return Err(ZipError::new(ZipErrorKind::PasswordRequired))
+                    };
+
+                    match encryption {
+                        ZipEncryption::ZipCrypto => {
+                            let mut zip_crypto_writer = ZipCryptoWriter::new(content_writer, password, self.crc32)?;
+
+                            self.encode(compression_method, codec_registry, &mut buf_reader, zip_options, thread_budget, &mut zip_crypto_writer)?;
+                        },
+                        ZipEncryption::WinZipAes(strength) => {
+                            let mut winzip_aes_writer = WinZipAesWriter::new(content_writer, password, strength)?;
+
+                            self.encode(compression_method, codec_registry, &mut buf_reader, zip_options, thread_budget, &mut winzip_aes_writer)?;
+                            winzip_aes_writer.finish().map_err(ZipError::from)?;
+                        }
+                    }
+                },
+                None => self.encode(compression_method, codec_registry, &mut buf_reader, zip_options, thread_budget, content_writer)?
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg_attr(not(feature = "deflate"), allow(unused_variables))]
+    fn encode<R, W>(&self, compression_method: &CompressionMethod, codec_registry: &CodecRegistry, reader: &mut R, zip_options: &ZipOptions, thread_budget: Option<&ThreadBudget>, writer: &mut W) -> Result<(), ZipError> where R: BufRead, W: Write {
+        // Large entries are always deflated in chunks, so the archive is the same whatever the number of threads is.
+        // The chunks are compressed on the permit of this worker and on the permits of idle workers.
+        #[cfg(feature = "deflate")]
+        {
+            if compression_method == &CompressionMethod::Deflate && self.uncompressed_size as u64 >= MIN_SIZE_TO_DEFLATE_IN_PARALLEL {
+                let borrowed_permits = thread_budget.map(|thread_budget| thread_budget.try_acquire(zip_options.threads() - 1));
+                let threads = 1 + borrowed_permits.as_ref().map_or(0, |borrowed_permits| borrowed_permits.count());
+                let checksum = deflate::deflate_in_parallel(reader, writer, zip_options.compression_options().compression_level(), threads)
+                    .map_err(ZipError::from)?;
+
+                if checksum != self.crc32 {
+                    return Err(ZipError::with_cause(ZipErrorKind::Io, "File content has changed while being zipped."));
+                }
+
+                return Ok(());
+            }
+        }
+
+        let codec = codec_registry.encoder(*compression_method)
+            .ok_or_else(|| unsupported_compression(*compression_method))?;
+        codec.encode(reader, writer, zip_options.compression_options())
+            .map_err(ZipError::from)?;
+
+        Ok(())
+    }
+}
+
 impl<'a> Iterator for ZipFileIntoIterator<'a> {
     type Item = &'a zip_item::ZipItem;
 
@@ -335,4 +594,83 @@ impl<'a> IntoIterator for &'a ZipFile {
     fn into_iter(self) -> Self::IntoIter {
         ZipFileIntoIterator::new(&self.zip_items)
     }
-}
\ No newline at end of file
+}
+
+// Sizes and offsets have 32 bits and the item count 16 bits until ZIP64 is supported.
+pub(crate) fn without_zip64<T, U>(value: T, name: &str) -> Result<U, ZipError>
+where U: TryFrom<T> {
+    U::try_from(value)
+        .map_err(|_| ZipError::with_cause(ZipErrorKind::Zip64Required, format!("{} does not fit in a ZIP file without ZIP64.", name)))
+}
+
+// Temporary files are only readable by their owner, ZIP files get the default permissions of new files instead.
+fn temp_file_builder() -> tempfile::Builder<'static, 'static> {
+    let mut builder = tempfile::Builder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(0o666));
+    }
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::{options::CompressionOptions, zip_item_creator::ZipItemCreator};
+
+    fn zip_directory(base_path: &Path, dest_path: &Path, threads: usize) -> Vec<u8> {
+        let base_path = base_path.to_path_buf();
+        let dest_path = dest_path.to_path_buf();
+        let zip_options = ZipOptions::new(&base_path, &dest_path, None, None, threads, CompressionOptions::default());
+
+        let mut zip_items = Vec::new();
+        ZipItemCreator::new(&base_path, zip_options.compression_options()).create_zip_items(&base_path, None, &mut zip_items, None).unwrap();
+        let mut zip_file = ZipFile::create(zip_items.len() as u16, zip_items, OsString::from(dest_path.as_os_str()), EncryptionMethod::NoEncryption);
+        zip_file.create_zip_file(&zip_options).unwrap();
+
+        std::fs::read(&dest_path).unwrap()
+    }
+
+    #[test]
+    fn zips_same_bytes_with_any_number_of_threads() {
+        let source_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(source_dir.path().join("docs")).unwrap();
+        std::fs::write(source_dir.path().join("docs/readme.txt"), b"Read me ".repeat(20000)).unwrap();
+        std::fs::write(source_dir.path().join("notes.txt"), b"Notes").unwrap();
+        std::fs::write(source_dir.path().join("empty.txt"), b"").unwrap();
+        // Random content is stored since deflating does not make it smaller.
+        std::fs::write(source_dir.path().join("random.bin"), (0..100_000).map(|_| rand::random::<u8>()).collect::<Vec<u8>>()).unwrap();
+        // Items of 8 MiB and more are deflated in chunks.
+        let large_content: Vec<u8> = (0..9 * 1024 * 1024).map(|index| ((index / 7) % 251) as u8 ^ (index % 13) as u8).collect();
+        std::fs::write(source_dir.path().join("large.bin"), large_content).unwrap();
+
+        let dest_dir = tempfile::tempdir().unwrap();
+        let single_threaded = zip_directory(source_dir.path(), &dest_dir.path().join("single.zip"), 1);
+        let multi_threaded = zip_directory(source_dir.path(), &dest_dir.path().join("multi.zip"), 4);
+
+        assert_eq!(single_threaded, multi_threaded);
+    }
+
+    #[test]
+    #[cfg(feature = "deflate")]
+    fn stores_content_that_does_not_get_smaller() {
+        let random_content: Vec<u8> = (0..100_000).map(|_| rand::random::<u8>()).collect();
+        let source_dir = tempfile::tempdir().unwrap();
+        std::fs::write(source_dir.path().join("a_random.bin"), &random_content).unwrap();
+        std::fs::write(source_dir.path().join("b_notes.txt"), b"Notes ".repeat(1000)).unwrap();
+
+        let dest_dir = tempfile::tempdir().unwrap();
+        for threads in [1, 2] {
+            let zip_bytes = zip_directory(source_dir.path(), &dest_dir.path().join(format!("{}.zip", threads)), threads);
+            let mut zip_file = ZipFile::from_reader(std::io::Cursor::new(zip_bytes)).unwrap();
+            assert_eq!(zip_file.item("a_random.bin").unwrap().compression_method(), CompressionMethod::NoCompression);
+            assert_eq!(zip_file.item("b_notes.txt").unwrap().compression_method(), CompressionMethod::Deflate);
+
+            let extract_dir = tempfile::tempdir().unwrap();
+            zip_file.extract_all(ExtractOptions::new(extract_dir.path(), Vec::new(), None, 1)).unwrap();
+            assert_eq!(std::fs::read(extract_dir.path().join("a_random.bin")).unwrap(), random_content);
+            assert_eq!(std::fs::read(extract_dir.path().join("b_notes.txt")).unwrap(), b"Notes ".repeat(1000));
+        }
+    }
+}
@@ -1,32 +1,300 @@
-use std::{ffi::OsString, path::{Path, PathBuf}};
+use std::{fmt::{self, Debug, Formatter}, path::{Path, PathBuf}, time::SystemTime};
+
+pub use glob::Pattern;
+use zeroize::Zeroizing;
+
+use super::{encryption::zip_crypto::ENCRYPTION_HEADER_SIZE, mem_map::{CompressionMethod, EncryptionMethod}, progress::ProgressObserver, cancellation::CancellationToken};
+
+pub use super::encryption::winzip_aes::AesStrength;
+
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 9;
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+// Extensions of formats that are already compressed, such files are stored without trying to compress them.
+pub const DEFAULT_STORE_EXTENSIONS: [&str; 21] = [
+    "jpg", "jpeg", "png", "gif", "webp", "zip", "gz", "tgz", "bz2", "xz", "zst",
+    "7z", "rar", "mp3", "mp4", "mkv", "mov", "avi", "webm", "woff", "woff2"
+];
+
+// A password whose buffer is zeroed once it is dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Password(Zeroizing<String>);
+
+impl Password {
+    pub fn new(password: String) -> Self {
+        Self(Zeroizing::new(password))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Password {
+    fn from(password: String) -> Self {
+        Password::new(password)
+    }
+}
+
+impl Debug for Password {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Password(***)")
+    }
+}
+
+// Asks for the password of an encrypted item once none of the known passwords is correct.
+pub trait PasswordPrompt {
+    // `retry` is true when the previously entered password was wrong. Returning None gives up on the item.
+    fn ask_password(&self, item_path: &str, retry: bool) -> Option<Password>;
+}
 
 pub struct ExtractOptions<'a> {
-    zip_file_path: OsString,
-    verbose_mode: bool,
     destination_path: &'a Path,
-    zip_password: Option<String>
+    passwords: Vec<Password>,
+    password_prompt: Option<&'a (dyn PasswordPrompt + Sync)>,
+    threads: usize,
+    progress_observer: Option<&'a dyn ProgressObserver>,
+    cancellation_token: Option<CancellationToken>
+}
+
+// How the items are encrypted while zipping.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ZipEncryption {
+    ZipCrypto,
+    WinZipAes(AesStrength)
+}
+
+impl ZipEncryption {
+    pub fn encryption_method(self) -> EncryptionMethod {
+        match self {
+            ZipEncryption::ZipCrypto => EncryptionMethod::ZipCrypto,
+            ZipEncryption::WinZipAes(_) => EncryptionMethod::WinZipAesEncryption
+        }
+    }
+
+    // Bytes the encryption adds to the compressed content of an item.
+    pub fn encryption_overhead(self) -> u64 {
+        match self {
+            ZipEncryption::ZipCrypto => ENCRYPTION_HEADER_SIZE,
+            ZipEncryption::WinZipAes(strength) => strength.encryption_overhead()
+        }
+    }
+}
+
+pub struct EncryptionOptions {
+    encryption: ZipEncryption,
+    patterns: Vec<Pattern>
+}
+
+impl EncryptionOptions {
+    pub fn new(encryption: ZipEncryption, patterns: Vec<Pattern>) -> Self {
+        Self {
+            encryption,
+            patterns
+        }
+    }
+
+    pub fn encryption(&self) -> ZipEncryption {
+        self.encryption
+    }
+
+    // Whether the item with the given path is encrypted, every item is when there are no patterns.
+    pub fn encrypts(&self, item_path: &str) -> bool {
+        self.patterns.is_empty() || self.patterns.iter().any(|pattern| pattern.matches(item_path))
+    }
+}
+
+// Options of an entry added with a ZipWriter.
+#[derive(Clone)]
+pub struct EntryOptions {
+    compression_method: CompressionMethod,
+    compression_level: Option<u32>,
+    modified_time: Option<SystemTime>,
+    unix_permissions: Option<u32>,
+    comment: String,
+    encryption: Option<ZipEncryption>,
+    password: Option<Password>
+}
+
+impl EntryOptions {
+    // Without a level the default level of the compression method is used.
+    pub fn new(compression_method: CompressionMethod, compression_level: Option<u32>) -> Self {
+        Self {
+            compression_method,
+            compression_level,
+            modified_time: None,
+            unix_permissions: None,
+            comment: String::new(),
+            encryption: None,
+            password: None
+        }
+    }
+
+    pub fn set_modified_time(&mut self, modified_time: SystemTime) {
+        self.modified_time = Some(modified_time);
+    }
+
+    // Permission bits of the entry on Unix, e.g. 0o644.
+    pub fn set_unix_permissions(&mut self, unix_permissions: u32) {
+        self.unix_permissions = Some(unix_permissions);
+    }
+
+    pub fn set_comment(&mut self, comment: String) {
+        self.comment = comment;
+    }
+
+    pub fn set_encryption(&mut self, encryption: ZipEncryption, password: Password) {
+        self.encryption = Some(encryption);
+        self.password = Some(password);
+    }
+
+    pub fn compression_method(&self) -> CompressionMethod {
+        self.compression_method
+    }
+
+    pub fn modified_time(&self) -> Option<SystemTime> {
+        self.modified_time
+    }
+
+    pub fn unix_permissions(&self) -> Option<u32> {
+        self.unix_permissions
+    }
+
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    pub fn encryption(&self) -> Option<ZipEncryption> {
+        self.encryption
+    }
+
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_ref().map(Password::as_str)
+    }
+
+    pub fn compression_options(&self) -> CompressionOptions {
+        let zstd_level = self.compression_level.map_or(DEFAULT_ZSTD_LEVEL, |compression_level| compression_level as i32);
+
+        CompressionOptions::new(Some(self.compression_method), self.compression_level.unwrap_or(DEFAULT_COMPRESSION_LEVEL), Vec::new(), ZstdOptions::new(zstd_level, None))
+    }
+}
+
+impl Default for EntryOptions {
+    fn default() -> Self {
+        let compression_method = if cfg!(feature = "deflate") { CompressionMethod::Deflate } else { CompressionMethod::NoCompression };
+
+        EntryOptions::new(compression_method, None)
+    }
 }
 
 pub struct ZipOptions<'a> {
     base_path: &'a PathBuf,
-    encrypt_file: bool,
+    encryption_options: Option<EncryptionOptions>,
     dest_path: &'a PathBuf,
-    password: Option<String>,
-    verbose_mode: bool
+    password: Option<Password>,
+    threads: usize,
+    compression_options: CompressionOptions,
+    progress_observer: Option<&'a dyn ProgressObserver>,
+    cancellation_token: Option<CancellationToken>
+}
+
+pub struct CompressionOptions {
+    compression_method: Option<CompressionMethod>,
+    compression_level: u32,
+    store_extensions: Vec<String>,
+    zstd_options: ZstdOptions
+}
+
+pub struct ZstdOptions {
+    level: i32,
+    long_distance_window_log: Option<u32>
+}
+
+impl ZstdOptions {
+
+    pub fn new(level: i32, long_distance_window_log: Option<u32>) -> Self {
+        Self {
+            level,
+            long_distance_window_log
+        }
+    }
+
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+
+    // Long distance matching is enabled when a window is given. The window is given as a power of two.
+    pub fn long_distance_window_log(&self) -> Option<u32> {
+        self.long_distance_window_log
+    }
+}
+
+impl Default for ZstdOptions {
+    fn default() -> Self {
+        ZstdOptions::new(DEFAULT_ZSTD_LEVEL, None)
+    }
+}
+
+impl CompressionOptions {
+
+    pub fn new(compression_method: Option<CompressionMethod>, compression_level: u32, store_extensions: Vec<String>, zstd_options: ZstdOptions) -> Self {
+        Self {
+            compression_method,
+            compression_level,
+            store_extensions: store_extensions.into_iter()
+                .map(|extension| extension.trim_start_matches('.').to_lowercase())
+                .collect(),
+            zstd_options
+        }
+    }
+
+    pub fn compression_method(&self) -> Option<CompressionMethod> {
+        self.compression_method
+    }
+
+    pub fn compression_level(&self) -> u32 {
+        self.compression_level
+    }
+
+    pub fn zstd_options(&self) -> &ZstdOptions {
+        &self.zstd_options
+    }
+
+    pub fn is_store_extension(&self, extension: &str) -> bool {
+        let extension = extension.to_lowercase();
+        self.store_extensions.contains(&extension)
+    }
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        CompressionOptions::new(None, DEFAULT_COMPRESSION_LEVEL, DEFAULT_STORE_EXTENSIONS.iter().map(|extension| extension.to_string()).collect(), ZstdOptions::default())
+    }
 }
 
 impl<'a> ZipOptions<'a> {
 
-    pub fn new(base_path: &'a PathBuf, dest_path: &'a PathBuf, encrypt_file: bool, password: Option<String>, verbose_mode: bool) -> Self {
+    pub fn new(base_path: &'a PathBuf, dest_path: &'a PathBuf, encryption_options: Option<EncryptionOptions>, password: Option<Password>, threads: usize, compression_options: CompressionOptions) -> Self {
         Self {
             base_path,
             dest_path,
-            encrypt_file,
+            encryption_options,
             password,
-            verbose_mode
+            threads,
+            compression_options,
+            progress_observer: None,
+            cancellation_token: None
         }
     }
 
+    pub fn set_progress_observer(&mut self, progress_observer: &'a dyn ProgressObserver) {
+        self.progress_observer = Some(progress_observer);
+    }
+
+    pub fn set_cancellation_token(&mut self, cancellation_token: CancellationToken) {
+        self.cancellation_token = Some(cancellation_token);
+    }
+
     pub fn base_path(&self) -> &PathBuf {
         self.base_path
     }
@@ -35,43 +303,175 @@ impl<'a> ZipOptions<'a> {
         self.dest_path
     }
 
-    pub fn password(&self) -> &Option<String> {
-        &self.password
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_ref().map(Password::as_str)
     }
 
     pub fn encrypt_file(&self) -> bool {
-        self.encrypt_file
+        self.encryption_options.is_some()
+    }
+
+    pub fn encryption_options(&self) -> Option<&EncryptionOptions> {
+        self.encryption_options.as_ref()
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn compression_options(&self) -> &CompressionOptions {
+        &self.compression_options
+    }
+
+    pub fn progress_observer(&self) -> Option<&'a dyn ProgressObserver> {
+        self.progress_observer
     }
 
-    pub fn verbose_mode(&self) -> bool {
-        self.verbose_mode
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
     }
 
 }
 
 impl<'a> ExtractOptions<'a> {
-    pub fn new(verbose_mode: bool, destination_path: &'a Path, zip_password: Option<String>, zip_file_path: OsString) -> Self {
+    // The passwords are tried in order on every encrypted item, the prompt is used once none of them is correct.
+    pub fn new(destination_path: &'a Path, passwords: Vec<Password>, password_prompt: Option<&'a (dyn PasswordPrompt + Sync)>, threads: usize) -> Self {
         Self {
-            verbose_mode,
             destination_path,
-            zip_password,
-            zip_file_path
+            passwords,
+            password_prompt,
+            threads,
+            progress_observer: None,
+            cancellation_token: None
         }
     }
 
-    pub fn verbose_mode(&self) -> bool {
-        self.verbose_mode
+    pub fn set_progress_observer(&mut self, progress_observer: &'a dyn ProgressObserver) {
+        self.progress_observer = Some(progress_observer);
+    }
+
+    pub fn set_cancellation_token(&mut self, cancellation_token: CancellationToken) {
+        self.cancellation_token = Some(cancellation_token);
     }
 
     pub fn destination_path(&self) -> &Path {
         self.destination_path
     }
 
-    pub fn zip_password(&self) -> &Option<String> {
-        &self.zip_password
+    pub fn passwords(&self) -> &[Password] {
+        &self.passwords
+    }
+
+    pub fn password_prompt(&self) -> Option<&'a (dyn PasswordPrompt + Sync)> {
+        self.password_prompt
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn progress_observer(&self) -> Option<&'a dyn ProgressObserver> {
+        self.progress_observer
     }
 
-    pub fn zip_file_path(&self) -> &OsString {
-        &self.zip_file_path
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
+    }
+}
+
+// Options of re-encrypting an archive, the items are decrypted with `passwords` and the prompt like when extracting.
+// Without an encryption the items are written decrypted.
+pub struct RecryptOptions<'a> {
+    dest_path: &'a Path,
+    passwords: Vec<Password>,
+    password_prompt: Option<&'a (dyn PasswordPrompt + Sync)>,
+    encryption: Option<ZipEncryption>,
+    new_password: Option<Password>,
+    progress_observer: Option<&'a dyn ProgressObserver>,
+    cancellation_token: Option<CancellationToken>
+}
+
+impl<'a> RecryptOptions<'a> {
+    pub fn new(dest_path: &'a Path, passwords: Vec<Password>, password_prompt: Option<&'a (dyn PasswordPrompt + Sync)>, encryption: Option<ZipEncryption>, new_password: Option<Password>) -> Self {
+        Self {
+            dest_path,
+            passwords,
+            password_prompt,
+            encryption,
+            new_password,
+            progress_observer: None,
+            cancellation_token: None
+        }
+    }
+
+    // Only the start and the end of every item are reported, the content is copied without being decompressed.
+    pub fn set_progress_observer(&mut self, progress_observer: &'a dyn ProgressObserver) {
+        self.progress_observer = Some(progress_observer);
+    }
+
+    pub fn set_cancellation_token(&mut self, cancellation_token: CancellationToken) {
+        self.cancellation_token = Some(cancellation_token);
+    }
+
+    pub fn dest_path(&self) -> &Path {
+        self.dest_path
+    }
+
+    pub fn passwords(&self) -> &[Password] {
+        &self.passwords
+    }
+
+    pub fn password_prompt(&self) -> Option<&'a (dyn PasswordPrompt + Sync)> {
+        self.password_prompt
+    }
+
+    pub fn encryption(&self) -> Option<ZipEncryption> {
+        self.encryption
+    }
+
+    pub fn new_password(&self) -> Option<&str> {
+        self.new_password.as_ref().map(Password::as_str)
+    }
+
+    pub fn progress_observer(&self) -> Option<&'a dyn ProgressObserver> {
+        self.progress_observer
+    }
+
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_extensions_match_case_insensitively() {
+        let compression_options = CompressionOptions::new(None, DEFAULT_COMPRESSION_LEVEL, vec![String::from(".JPG"), String::from("png")], ZstdOptions::default());
+
+        assert!(compression_options.is_store_extension("jpg"));
+        assert!(compression_options.is_store_extension("PNG"));
+        assert!(!compression_options.is_store_extension("txt"));
+    }
+
+    #[test]
+    fn password_is_not_printed() {
+        let password = Password::new(String::from("swordfish"));
+
+        assert_eq!(password.as_str(), "swordfish");
+        assert!(!format!("{:?}", password).contains("swordfish"));
+    }
+
+    #[test]
+    fn encryption_patterns_select_items() {
+        let all = EncryptionOptions::new(ZipEncryption::ZipCrypto, vec![]);
+        assert!(all.encrypts("docs/readme.txt"));
+
+        let patterns = vec![Pattern::new("*.key").unwrap(), Pattern::new("secret/*").unwrap()];
+        let some = EncryptionOptions::new(ZipEncryption::ZipCrypto, patterns);
+        assert!(some.encrypts("server.key"));
+        assert!(some.encrypts("secret/notes.txt"));
+        assert!(!some.encrypts("docs/readme.txt"));
     }
 }
\ No newline at end of file
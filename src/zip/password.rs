@@ -0,0 +1,171 @@
+use std::{io::{Read, Seek}, sync::Mutex};
+
+use zip::{ZipError, ZipErrorKind, mem_map::EncryptionMethod, options::{Password, PasswordPrompt}, zip_item::ZipItem};
+
+// Finds the password of every encrypted item, items of an archive may be encrypted with different passwords.
+// The known passwords are tried first, the prompt is asked once none of them is correct.
+pub struct PasswordResolver<'a> {
+    passwords: Mutex<Vec<Password>>,
+    password_prompt: Option<&'a (dyn PasswordPrompt + Sync)>,
+    prompt_lock: Mutex<()>
+}
+
+impl<'a> PasswordResolver<'a> {
+    pub fn new(passwords: Vec<Password>, password_prompt: Option<&'a (dyn PasswordPrompt + Sync)>) -> Self {
+        Self {
+            passwords: Mutex::new(passwords),
+            password_prompt,
+            prompt_lock: Mutex::new(())
+        }
+    }
+
+    // Returns None for items that are not encrypted.
+    pub fn resolve<R>(&self, item: &ZipItem, zip_file_reader: &mut R) -> Result<Option<Password>, ZipError>
+    where R: Read + Seek {
+        if item.encryption_method() == EncryptionMethod::NoEncryption {
+            return Ok(None);
+        }
+
+        let mut tried_passwords = Vec::new();
+        if let Some(password) = self.try_known_passwords(item, &mut tried_passwords, zip_file_reader)? {
+            return Ok(Some(password));
+        }
+
+        let password_prompt = match self.password_prompt {
+            Some(password_prompt) => password_prompt,
+            None if tried_passwords.is_empty() => return Err(ZipError::with_cause(ZipErrorKind::PasswordRequired, format!("{} is encrypted.", item.item_path()))),
+            None => return Err(invalid_password(item))
+        };
+
+        // Only one item asks for its password at a time, the password entered for another item may fit this one too.
+        let _prompt_guard = self.prompt_lock.lock().unwrap();
+        if let Some(password) = self.try_known_passwords(item, &mut tried_passwords, zip_file_reader)? {
+            return Ok(Some(password));
+        }
+
+        let mut retry = false;
+        while let Some(password) = password_prompt.ask_password(item.item_path(), retry) {
+            if self.check_password(item, &password, zip_file_reader)? {
+                // Items may share the entered password, it is tried first from now on.
+                self.passwords.lock().unwrap().insert(0, password.clone());
+                return Ok(Some(password));
+            }
+            retry = true;
+        }
+
+        Err(invalid_password(item))
+    }
+
+    // Skips the passwords tried before, a correct password is moved to the front as the next item is likely to use it too.
+    fn try_known_passwords<R>(&self, item: &ZipItem, tried_passwords: &mut Vec<Password>, zip_file_reader: &mut R) -> Result<Option<Password>, ZipError>
+    where R: Read + Seek {
+        let passwords = self.passwords.lock().unwrap().clone();
+
+        for password in passwords {
+            if tried_passwords.contains(&password) {
+                continue;
+            }
+
+            if self.check_password(item, &password, zip_file_reader)? {
+                let mut passwords = self.passwords.lock().unwrap();
+                if let Some(index) = passwords.iter().position(|known_password| known_password == &password) {
+                    let password = passwords.remove(index);
+                    passwords.insert(0, password);
+                }
+                return Ok(Some(password));
+            }
+            tried_passwords.push(password);
+        }
+
+        Ok(None)
+    }
+
+    fn check_password<R>(&self, item: &ZipItem, password: &Password, zip_file_reader: &mut R) -> Result<bool, ZipError>
+    where R: Read + Seek {
+        match item.check_password(Some(password.as_str()), zip_file_reader) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == ZipErrorKind::IncorrectPassword => Ok(false),
+            Err(err) => Err(err)
+        }
+    }
+}
+
+fn invalid_password(item: &ZipItem) -> ZipError {
+    ZipError::with_cause(ZipErrorKind::IncorrectPassword, format!("No correct password for {}.", item.item_path()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::{date_time::ZipDateTime, encryption::winzip_aes::{AesExtraField, AesStrength, AE_2_VENDOR_VERSION}, local_file_header::LocalFileHeader, mem_map::CompressionMethod};
+
+    // "Hello WinZip AES-256" encrypted with the password "secret".
+    const ENCRYPTED_CONTENT: [u8; 48] = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, 0x10, 0x6B, 0xB8, 0x0B, 0xF4,
+        0x07, 0x0C, 0x57, 0x29, 0x63, 0xBF, 0x7A, 0xAD, 0xAE, 0x61, 0xF8, 0xE6, 0xD4, 0x9E, 0x92, 0xFE, 0x08, 0xE5, 0xD3, 0x12, 0xAB, 0x9E, 0x16, 0x96, 0x18, 0x13, 0x84, 0x9F];
+
+    struct ScriptedPrompt {
+        answers: Mutex<Vec<&'static str>>,
+        retries: Mutex<Vec<bool>>
+    }
+
+    impl PasswordPrompt for ScriptedPrompt {
+        fn ask_password(&self, _item_path: &str, retry: bool) -> Option<Password> {
+            self.retries.lock().unwrap().push(retry);
+            self.answers.lock().unwrap().pop().map(|answer| Password::new(String::from(answer)))
+        }
+    }
+
+    fn password(password: &str) -> Password {
+        Password::new(String::from(password))
+    }
+
+    fn encrypted_item() -> (ZipItem, Cursor<Vec<u8>>) {
+        let mut item = ZipItem::new(CompressionMethod::NoCompression, String::from("secret.txt"), 20, ENCRYPTED_CONTENT.len() as u32,
+            ZipDateTime::new(1, 1, 2021, 0, 0, 0), 0, EncryptionMethod::WinZipAesEncryption, 0);
+        item.update_aes_extra_field(Some(AesExtraField::new(AE_2_VENDOR_VERSION, AesStrength::Aes256, CompressionMethod::NoCompression)));
+
+        let mut archive = LocalFileHeader::from_zip_item(&item).to_binary();
+        archive.extend_from_slice(&ENCRYPTED_CONTENT);
+        (item, Cursor::new(archive))
+    }
+
+    #[test]
+    fn finds_password_among_candidates() {
+        let (item, mut reader) = encrypted_item();
+        let resolver = PasswordResolver::new(vec![password("wrong"), password("secret")], None);
+
+        assert_eq!(resolver.resolve(&item, &mut reader).unwrap(), Some(password("secret")));
+        assert_eq!(resolver.passwords.lock().unwrap()[0], password("secret"));
+    }
+
+    #[test]
+    fn prompts_again_until_password_is_correct() {
+        let (item, mut reader) = encrypted_item();
+        let prompt = ScriptedPrompt { answers: Mutex::new(vec!["secret", "Secret"]), retries: Mutex::new(Vec::new()) };
+        let resolver = PasswordResolver::new(vec![password("wrong")], Some(&prompt));
+
+        assert_eq!(resolver.resolve(&item, &mut reader).unwrap(), Some(password("secret")));
+        assert_eq!(*prompt.retries.lock().unwrap(), vec![false, true]);
+    }
+
+    #[test]
+    fn fails_once_prompt_gives_up() {
+        let (item, mut reader) = encrypted_item();
+        let prompt = ScriptedPrompt { answers: Mutex::new(vec!["wrong"]), retries: Mutex::new(Vec::new()) };
+        let resolver = PasswordResolver::new(Vec::new(), Some(&prompt));
+
+        let result = resolver.resolve(&item, &mut reader);
+
+        assert_eq!(result.unwrap_err().kind(), ZipErrorKind::IncorrectPassword);
+        assert_eq!(*prompt.retries.lock().unwrap(), vec![false, true]);
+    }
+
+    #[test]
+    fn requires_password_without_candidates() {
+        let (item, mut reader) = encrypted_item();
+        let resolver = PasswordResolver::new(Vec::new(), None);
+
+        assert_eq!(resolver.resolve(&item, &mut reader).unwrap_err().kind(), ZipErrorKind::PasswordRequired);
+    }
+}
@@ -0,0 +1,136 @@
+use std::io::{Read, Write};
+
+// Receives the progress of extracting, zipping and re-encrypting ZIP files, e.g. to render a progress bar.
+// Items are handled on several threads when more than one thread is used, the events of different items may then interleave.
+pub trait ProgressObserver: Sync {
+    // `size` is the uncompressed size of the item.
+    fn entry_started(&self, _item_path: &str, _size: u64) {}
+
+    // Uncompressed bytes of an item read or written since the last call, only sent while extracting and zipping.
+    fn bytes_processed(&self, _bytes: u64) {}
+
+    fn entry_finished(&self, _item_path: &str) {}
+}
+
+// Reports the bytes read through it to the observer.
+pub(crate) struct ProgressReader<'a, R> {
+    reader: R,
+    progress_observer: Option<&'a dyn ProgressObserver>
+}
+
+impl<'a, R> ProgressReader<'a, R> {
+    pub fn new(reader: R, progress_observer: Option<&'a dyn ProgressObserver>) -> Self {
+        Self {
+            reader,
+            progress_observer
+        }
+    }
+}
+
+impl<'a, R: Read> Read for ProgressReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let bytes_read = self.reader.read(buf)?;
+
+        if let Some(progress_observer) = self.progress_observer.filter(|_| bytes_read > 0) {
+            progress_observer.bytes_processed(bytes_read as u64);
+        }
+
+        Ok(bytes_read)
+    }
+}
+
+// Reports the bytes written through it to the observer.
+pub(crate) struct ProgressWriter<'a, W> {
+    writer: W,
+    progress_observer: Option<&'a dyn ProgressObserver>
+}
+
+impl<'a, W> ProgressWriter<'a, W> {
+    pub fn new(writer: W, progress_observer: Option<&'a dyn ProgressObserver>) -> Self {
+        Self {
+            writer,
+            progress_observer
+        }
+    }
+}
+
+impl<'a, W: Write> Write for ProgressWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let bytes_written = self.writer.write(buf)?;
+
+        if let Some(progress_observer) = self.progress_observer.filter(|_| bytes_written > 0) {
+            progress_observer.bytes_processed(bytes_written as u64);
+        }
+
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ffi::OsString, io::Cursor, sync::{Mutex, atomic::{AtomicU64, Ordering}}};
+    use zip::{ZipFile, mem_map::EncryptionMethod, options::{CompressionOptions, EntryOptions, ExtractOptions, ZipOptions}, writer::ZipWriter, zip_item_creator::ZipItemCreator};
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: Mutex<Vec<String>>,
+        bytes: AtomicU64
+    }
+
+    impl ProgressObserver for RecordingObserver {
+        fn entry_started(&self, item_path: &str, size: u64) {
+            self.events.lock().unwrap().push(format!("started {} {}", item_path, size));
+        }
+
+        fn bytes_processed(&self, bytes: u64) {
+            self.bytes.fetch_add(bytes, Ordering::SeqCst);
+        }
+
+        fn entry_finished(&self, item_path: &str) {
+            self.events.lock().unwrap().push(format!("finished {}", item_path));
+        }
+    }
+
+    #[test]
+    fn reports_entries_and_bytes_while_extracting() {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.add_directory("docs", &EntryOptions::default()).unwrap();
+        zip_writer.add_bytes("docs/readme.txt", &b"Read me ".repeat(1000), &EntryOptions::default()).unwrap();
+        let mut zip_file = ZipFile::from_reader(Cursor::new(zip_writer.finish().unwrap().into_inner())).unwrap();
+
+        let observer = RecordingObserver::default();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let mut options = ExtractOptions::new(dest_dir.path(), Vec::new(), None, 1);
+        options.set_progress_observer(&observer);
+        zip_file.extract_all(options).unwrap();
+
+        assert_eq!(*observer.events.lock().unwrap(), vec!["started docs/ 0", "finished docs/", "started docs/readme.txt 8000", "finished docs/readme.txt"]);
+        assert_eq!(observer.bytes.load(Ordering::SeqCst), 8000);
+    }
+
+    #[test]
+    fn reports_entries_and_bytes_while_zipping() {
+        let source_dir = tempfile::tempdir().unwrap();
+        std::fs::write(source_dir.path().join("notes.txt"), b"Notes ".repeat(500)).unwrap();
+        let base_path = source_dir.path().to_path_buf();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let dest_path = dest_dir.path().join("notes.zip");
+
+        let mut zip_options = ZipOptions::new(&base_path, &dest_path, None, None, 2, CompressionOptions::default());
+        let observer = RecordingObserver::default();
+        zip_options.set_progress_observer(&observer);
+
+        let mut zip_items = Vec::new();
+        ZipItemCreator::new(&base_path, zip_options.compression_options()).create_zip_items(&base_path, None, &mut zip_items, None).unwrap();
+        let mut zip_file = ZipFile::create(zip_items.len() as u16, zip_items, OsString::from(dest_path.as_os_str()), EncryptionMethod::NoEncryption);
+        zip_file.create_zip_file(&zip_options).unwrap();
+
+        assert_eq!(*observer.events.lock().unwrap(), vec!["started notes.txt 3000", "finished notes.txt"]);
+        assert_eq!(observer.bytes.load(Ordering::SeqCst), 3000);
+    }
+}
@@ -0,0 +1,202 @@
+use std::{io::{BufWriter, Read, Seek, Write}, path::Path};
+
+use tempfile::NamedTempFile;
+
+use zip::{ZipError, ZipErrorKind, ZipFile, cancellation::{CancellableReader, check_cancelled}, crc32::ChecksumWriter, encryption::{winzip_aes::WinZipAesWriter, zip_crypto::ZipCryptoWriter}, options::{Password, RecryptOptions, ZipEncryption}, password::PasswordResolver, zip_item::ZipItem};
+
+impl ZipFile {
+    // Writes a copy of the archive whose files are encrypted as given, e.g. to change the password of the archive or to decrypt it.
+    // The compressed content is copied without recompressing it, AE-2 items are only decompressed to calculate the CRC32 they do not store.
+    // The copy is written to a temporary file next to the destination first, so the destination may be the archive itself.
+    pub fn recrypt(&self, options: &RecryptOptions) -> Result<(), ZipError> {
+        if options.encryption().is_some() && options.new_password().is_none() {
+            return Err(ZipError::new(ZipErrorKind::PasswordRequired));
+        }
+
+        let mut zip_file_reader = self.source.reader()?;
+        let password_resolver = PasswordResolver::new(options.passwords().to_vec(), options.password_prompt());
+
+        let dest_dir = match options.dest_path().parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new(".")
+        };
+        let temp_file = NamedTempFile::new_in(dest_dir).map_err(ZipError::from)?;
+        let mut file_writer = BufWriter::new(temp_file.as_file());
+        let mut cdfh_vec = Vec::with_capacity(self.zip_items.len());
+
+        for item in &self.zip_items {
+            check_cancelled(options.cancellation_token())?;
+
+            let zip_password = password_resolver.resolve(item, &mut zip_file_reader)
+                .map_err(|err| err.at_item(item))?;
+            let zip_password = zip_password.as_ref().map(Password::as_str);
+            let encryption = options.encryption().filter(|_| item.is_file());
+
+            let mut recrypted_item = ZipItem::new(item.compression_method(), item.item_path().clone(), item.uncompressed_size(), item.compressed_size(),
+                item.modified_date_time().to_owned(), item.start_offset(), item.encryption_method(), item.crc32());
+            if item.is_file() && !item.has_crc32() && !matches!(encryption, Some(ZipEncryption::WinZipAes(_))) {
+                recrypted_item.update_crc32(self.calculate_crc32(item, zip_password, &mut zip_file_reader)?);
+            }
+            recrypted_item.update_encryption(encryption);
+            recrypted_item.update_unix_permissions(item.unix_permissions());
+            recrypted_item.update_comment(item.comment().to_owned());
+            recrypted_item.update_compression_flags(item.compression_flags());
+
+            if let Some(progress_observer) = options.progress_observer() {
+                progress_observer.entry_started(item.item_path(), item.uncompressed_size() as u64);
+            }
+
+            let crc32 = recrypted_item.crc32();
+            let cdfh = ZipFile::write_zip_item(&mut file_writer, &mut recrypted_item, |writer, _| {
+                let (mut content_reader, _) = item.content_reader(zip_password, &mut zip_file_reader)
+                    .map_err(|err| err.at_item(item))?;
+                let mut content_reader = CancellableReader::new(&mut content_reader, options.cancellation_token());
+
+                ZipFile::copy_encrypted(&mut content_reader, writer, encryption, options.new_password(), crc32)
+            })?;
+            cdfh_vec.push(cdfh);
+
+            if let Some(progress_observer) = options.progress_observer() {
+                progress_observer.entry_finished(item.item_path());
+            }
+        }
+
+        ZipFile::write_central_directory(&mut file_writer, cdfh_vec)?;
+        file_writer.flush().map_err(ZipError::from)?;
+        drop(file_writer);
+        drop(zip_file_reader);
+
+        // Temporary files are only readable by their owner, the copy gets the permissions of the archive instead.
+        if let Some(zip_file_path) = self.source.path() {
+            let permissions = std::fs::metadata(zip_file_path)
+                .map_err(ZipError::from)?
+                .permissions();
+            temp_file.as_file().set_permissions(permissions).map_err(ZipError::from)?;
+        }
+
+        temp_file.persist(options.dest_path())
+            .map(|_| ())
+            .map_err(|err| ZipError::from(err.error))
+    }
+
+    fn calculate_crc32<R>(&self, item: &ZipItem, zip_password: Option<&str>, zip_file_reader: &mut R) -> Result<u32, ZipError>
+    where R: Read + Seek {
+        let mut checksum_writer = ChecksumWriter::new();
+        item.decompress_to(zip_password, &self.codec_registry, zip_file_reader, &mut checksum_writer)
+            .map_err(|err| err.at_item(item))?;
+
+        Ok(checksum_writer.checksum())
+    }
+
+    fn copy_encrypted<R, W>(content_reader: &mut R, writer: &mut W, encryption: Option<ZipEncryption>, password: Option<&str>, crc32: u32) -> Result<(), ZipError>
+    where R: Read, W: Write {
+        let encryption = match encryption {
+            Some(encryption) => encryption,
+            None => return std::io::copy(content_reader, writer).map(|_| ()).map_err(ZipError::from)
+        };
+        let password = password.ok_or_else(|| ZipError::new(ZipErrorKind::PasswordRequired))?;
+
+        match encryption {
+            ZipEncryption::ZipCrypto => {
+                let mut zip_crypto_writer = ZipCryptoWriter::new(writer, password, crc32)?;
+                std::io::copy(content_reader, &mut zip_crypto_writer).map_err(ZipError::from)?;
+            },
+            ZipEncryption::WinZipAes(strength) => {
+                let mut winzip_aes_writer = WinZipAesWriter::new(writer, password, strength)?;
+                std::io::copy(content_reader, &mut winzip_aes_writer).map_err(ZipError::from)?;
+                winzip_aes_writer.finish().map_err(ZipError::from)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use byteorder::{ByteOrder, LittleEndian};
+    use zip::{encryption::{winzip_aes::WinZipAesReader, zip_crypto::ZipCryptoReader}, mem_map::EncryptionMethod, options::{AesStrength, EntryOptions, ExtractOptions}, writer::ZipWriter};
+
+    // Sets compression flags on the files of the archive in both headers, like archivers recording the compression level.
+    fn set_compression_flags(zip_bytes: &mut [u8], compression_flags: u16) {
+        let eocd_offset = zip_bytes.len() - 22;
+        let mut cdfh_offset = LittleEndian::read_u32(&zip_bytes[eocd_offset + 16..eocd_offset + 20]) as usize;
+
+        while cdfh_offset < eocd_offset {
+            let name_length = LittleEndian::read_u16(&zip_bytes[cdfh_offset + 28..cdfh_offset + 30]) as usize;
+            let extra_field_length = LittleEndian::read_u16(&zip_bytes[cdfh_offset + 30..cdfh_offset + 32]) as usize;
+            let comment_length = LittleEndian::read_u16(&zip_bytes[cdfh_offset + 32..cdfh_offset + 34]) as usize;
+            let local_header_offset = LittleEndian::read_u32(&zip_bytes[cdfh_offset + 42..cdfh_offset + 46]) as usize;
+
+            if zip_bytes[cdfh_offset + 46 + name_length - 1] != b'/' {
+                for flag_offset in [cdfh_offset + 8, local_header_offset + 6] {
+                    let flag = LittleEndian::read_u16(&zip_bytes[flag_offset..flag_offset + 2]);
+                    LittleEndian::write_u16(&mut zip_bytes[flag_offset..flag_offset + 2], flag | compression_flags);
+                }
+            }
+            cdfh_offset += 46 + name_length + extra_field_length + comment_length;
+        }
+    }
+
+    #[test]
+    fn recrypts_archive_that_extracts_again() {
+        let content = b"Read me ".repeat(1000);
+        let mut entry_options = EntryOptions::default();
+        entry_options.set_unix_permissions(0o640);
+        entry_options.set_comment(String::from("Read me first"));
+
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.add_directory("docs", &entry_options).unwrap();
+        zip_writer.add_bytes("docs/readme.txt", &content, &entry_options).unwrap();
+        let mut zip_bytes = zip_writer.finish().unwrap().into_inner();
+        set_compression_flags(&mut zip_bytes, 0x02);
+
+        let dest_dir = tempfile::tempdir().unwrap();
+        let recrypted_path = dest_dir.path().join("recrypted.zip");
+        let options = RecryptOptions::new(&recrypted_path, Vec::new(), None, Some(ZipEncryption::WinZipAes(AesStrength::Aes256)), Some(Password::new(String::from("new"))));
+        ZipFile::from_reader(Cursor::new(zip_bytes)).unwrap().recrypt(&options).unwrap();
+
+        let mut recrypted = ZipFile::new(&recrypted_path).unwrap();
+        let items: Vec<&ZipItem> = recrypted.iter().collect();
+        assert_eq!(items.len(), 2);
+        for item in &items {
+            assert_eq!(item.unix_permissions(), Some(0o640));
+            assert_eq!(item.comment(), "Read me first");
+        }
+        assert_eq!(items[1].encryption_method(), EncryptionMethod::WinZipAesEncryption);
+        assert_eq!(items[1].general_purpose_flag() & 0x06, 0x02);
+
+        let extract_dir = tempfile::tempdir().unwrap();
+        recrypted.extract_all(ExtractOptions::new(extract_dir.path(), vec![Password::new(String::from("new"))], None, 1)).unwrap();
+        assert_eq!(std::fs::read(extract_dir.path().join("docs/readme.txt")).unwrap(), content);
+        assert!(recrypted.test_all(vec![Password::new(String::from("new"))], None).unwrap().iter().all(|(_, result)| result.is_ok()));
+    }
+
+    #[test]
+    fn copies_content_with_new_encryption() {
+        let content = b"compressed content copied as it is".to_vec();
+
+        let mut aes_encrypted = Vec::new();
+        ZipFile::copy_encrypted(&mut Cursor::new(&content), &mut aes_encrypted, Some(ZipEncryption::WinZipAes(AesStrength::Aes256)), Some("new"), 0).unwrap();
+        let mut decrypted = Vec::new();
+        WinZipAesReader::new("new", AesStrength::Aes256, aes_encrypted.len() as u64, Cursor::new(aes_encrypted)).unwrap()
+            .read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, content);
+
+        let mut zip_crypto_encrypted = Vec::new();
+        ZipFile::copy_encrypted(&mut Cursor::new(&content), &mut zip_crypto_encrypted, Some(ZipEncryption::ZipCrypto), Some("new"), 0x12345678).unwrap();
+        let mut decrypted = Vec::new();
+        ZipCryptoReader::new("new", 0x12345678, Cursor::new(zip_crypto_encrypted)).unwrap()
+            .read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, content);
+    }
+
+    #[test]
+    fn encrypting_requires_new_password() {
+        let result = ZipFile::copy_encrypted(&mut Cursor::new(Vec::new()), &mut Vec::new(), Some(ZipEncryption::ZipCrypto), None, 0);
+
+        assert_eq!(result.unwrap_err().kind(), ZipErrorKind::PasswordRequired);
+    }
+}
@@ -0,0 +1,103 @@
+use std::{ffi::OsString, fmt::{self, Debug, Formatter}, fs::File, io::{BufReader, Read, Seek, SeekFrom}, sync::{Mutex, MutexGuard, TryLockError}};
+
+use zip::error::{ZipError, ZipErrorKind};
+
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+// Where the items of a ZIP file are read from.
+pub enum ZipSource {
+    // Every reader opens the file again, so that items can be read on several threads.
+    Path(OsString),
+    // Readers share the given reader, one at a time.
+    Reader(Mutex<Box<dyn ReadSeek + Send>>)
+}
+
+impl ZipSource {
+    // The shared reader is not waited for, as it may be held by an entry reader of the calling thread,
+    // so ZIP files read with `from_reader` fail with ReaderInUse while one of their entry readers exists.
+    pub fn reader(&self) -> Result<ZipSourceReader<'_>, ZipError> {
+        match self {
+            ZipSource::Path(path) => File::open(path).map(|file| ZipSourceReader::File(BufReader::new(file))).map_err(ZipError::from),
+            ZipSource::Reader(reader) => match reader.try_lock() {
+                Ok(reader) => Ok(ZipSourceReader::Shared(reader)),
+                // Every read seeks first, so a reader left by a panic can be used again.
+                Err(TryLockError::Poisoned(poisoned)) => Ok(ZipSourceReader::Shared(poisoned.into_inner())),
+                Err(TryLockError::WouldBlock) => Err(ZipError::with_cause(ZipErrorKind::ReaderInUse, "drop the entry reader of this ZIP file before reading it again"))
+            }
+        }
+    }
+
+    // Whether several readers can be used at the same time.
+    pub fn is_reopenable(&self) -> bool {
+        matches!(self, ZipSource::Path(_))
+    }
+
+    pub fn path(&self) -> Option<&OsString> {
+        match self {
+            ZipSource::Path(path) => Some(path),
+            ZipSource::Reader(_) => None
+        }
+    }
+}
+
+impl Debug for ZipSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ZipSource::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            ZipSource::Reader(_) => f.write_str("Reader")
+        }
+    }
+}
+
+pub enum ZipSourceReader<'a> {
+    File(BufReader<File>),
+    Shared(MutexGuard<'a, Box<dyn ReadSeek + Send>>)
+}
+
+impl<'a> Read for ZipSourceReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            ZipSourceReader::File(reader) => reader.read(buf),
+            ZipSourceReader::Shared(reader) => reader.read(buf)
+        }
+    }
+}
+
+impl<'a> Seek for ZipSourceReader<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            ZipSourceReader::File(reader) => reader.seek(pos),
+            ZipSourceReader::Shared(reader) => reader.seek(pos)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use zip::{ZipFile, options::ExtractOptions};
+
+    // A ZIP file with hello.txt stored in it, containing "Hello ruzito".
+    const ZIP_FILE: [u8; 128] = [0x50, 0x4B, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x21, 0x52, 0xC7, 0x93, 0xC1, 0x79, 0x0C, 0x00,
+        0x00, 0x00, 0x0C, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x68, 0x65, 0x6C, 0x6C, 0x6F, 0x2E, 0x74, 0x78, 0x74, 0x48, 0x65, 0x6C, 0x6C, 0x6F,
+        0x20, 0x72, 0x75, 0x7A, 0x69, 0x74, 0x6F, 0x50, 0x4B, 0x01, 0x02, 0x14, 0x03, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x21, 0x52, 0xC7,
+        0x93, 0xC1, 0x79, 0x0C, 0x00, 0x00, 0x00, 0x0C, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x68, 0x65, 0x6C, 0x6C, 0x6F, 0x2E, 0x74, 0x78, 0x74, 0x50, 0x4B, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+        0x01, 0x00, 0x37, 0x00, 0x00, 0x00, 0x33, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn reads_zip_file_from_memory() {
+        let mut zip_file = ZipFile::from_reader(Cursor::new(ZIP_FILE.to_vec())).unwrap();
+
+        assert!(zip_file.zip_file_path().is_none());
+        assert_eq!(zip_file.iter().map(|item| item.item_path().as_str()).collect::<Vec<_>>(), vec!["hello.txt"]);
+        assert!(zip_file.test_all(Vec::new(), None).unwrap().iter().all(|(_, result)| result.is_ok()));
+
+        let dest_dir = tempfile::tempdir().unwrap();
+        zip_file.extract_all(ExtractOptions::new(dest_dir.path(), Vec::new(), None, 4)).unwrap();
+
+        assert_eq!(std::fs::read_to_string(dest_dir.path().join("hello.txt")).unwrap(), "Hello ruzito");
+    }
+}
@@ -0,0 +1,283 @@
+use std::{fs::{File, Metadata}, io::{BufRead, BufReader, Error, ErrorKind, Read, Seek, Write}, path::Path, time::SystemTime};
+
+use zip::{ZipError, ZipErrorKind, ZipFile, without_zip64, central_dir_file_header::CentralDirectoryFileHeader, codec::{Codec, CodecRegistry}, compression_pool::CompressedContentWriter, crc32::ChecksumReader, date_time::ZipDateTime, encryption::{winzip_aes::WinZipAesWriter, zip_crypto::ZipCryptoWriter}, mem_map::{CompressionMethod, EncryptionMethod}, options::{CompressionOptions, EntryOptions, ZipEncryption}, zip_item::{ZipItem, unsupported_compression}};
+
+// Writes a ZIP file entry by entry into any Write + Seek sink, e.g. a File or a Cursor over a Vec.
+// The ZIP file is only complete once `finish` has written the central directory.
+pub struct ZipWriter<W: Write + Seek> {
+    writer: W,
+    codec_registry: CodecRegistry,
+    cdfh_vec: Vec<CentralDirectoryFileHeader>,
+    started_file: Option<StartedFile>
+}
+
+// A file whose content is written to the ZipWriter itself.
+struct StartedFile {
+    item_path: String,
+    options: EntryOptions,
+    content: CompressedContentWriter
+}
+
+impl<W> ZipWriter<W> where W: Write + Seek {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            codec_registry: CodecRegistry::default(),
+            cdfh_vec: Vec::new(),
+            started_file: None
+        }
+    }
+
+    pub fn set_codec_registry(&mut self, codec_registry: CodecRegistry) {
+        self.codec_registry = codec_registry;
+    }
+
+    // Starts a file whose content is then written to the returned writer.
+    // The content is buffered, the file is added once the next entry is added or the ZIP file is finished.
+    pub fn start_file(&mut self, item_path: &str, options: &EntryOptions) -> Result<&mut Self, ZipError> {
+        self.finish_file()?;
+        self.started_file = Some(StartedFile {
+            item_path: String::from(item_path),
+            options: options.clone(),
+            content: CompressedContentWriter::new()
+        });
+
+        Ok(self)
+    }
+
+    pub fn add_bytes(&mut self, item_path: &str, content: &[u8], options: &EntryOptions) -> Result<(), ZipError> {
+        self.add_reader(item_path, content, options)
+    }
+
+    // Files are modified now unless the options give another time.
+    pub fn add_reader<R>(&mut self, item_path: &str, reader: R, options: &EntryOptions) -> Result<(), ZipError>
+    where R: Read {
+        let modified_time = options.modified_time().unwrap_or_else(SystemTime::now);
+        self.add_file(item_path, reader, options, modified_time, options.unix_permissions())
+    }
+
+    // The modification time and the permissions of the file on disk are kept unless the options give others.
+    pub fn add_path<P>(&mut self, item_path: &str, path: P, options: &EntryOptions) -> Result<(), ZipError>
+    where P: AsRef<Path> {
+        let file = File::open(path).map_err(ZipError::from)?;
+        let metadata = file.metadata().map_err(ZipError::from)?;
+
+        let modified_time = match options.modified_time() {
+            Some(modified_time) => modified_time,
+            None => metadata.modified().map_err(ZipError::from)?
+        };
+        let unix_permissions = options.unix_permissions().or_else(|| unix_permissions(&metadata));
+
+        self.add_file(item_path, BufReader::new(file), options, modified_time, unix_permissions)
+    }
+
+    // Only the modification time, the permissions and the comment of the options apply to directories.
+    pub fn add_directory(&mut self, item_path: &str, options: &EntryOptions) -> Result<(), ZipError> {
+        self.finish_file()?;
+
+        let mut item_path = String::from(item_path);
+        if !item_path.ends_with('/') {
+            item_path.push('/');
+        }
+
+        let modified_time = options.modified_time().unwrap_or_else(SystemTime::now);
+        let mut zip_item = ZipWriter::<W>::new_zip_item(item_path, CompressionMethod::NoCompression, modified_time, options.unix_permissions(), options)?;
+
+        let cdfh = ZipFile::write_zip_item(&mut self.writer, &mut zip_item, |_, _| Ok(()))?;
+        self.cdfh_vec.push(cdfh);
+
+        Ok(())
+    }
+
+    // Writes the central directory and gives the sink back.
+    pub fn finish(mut self) -> Result<W, ZipError> {
+        self.finish_file()?;
+        ZipFile::write_central_directory(&mut self.writer, std::mem::take(&mut self.cdfh_vec))?;
+        self.writer.flush().map_err(ZipError::from)?;
+
+        Ok(self.writer)
+    }
+
+    fn finish_file(&mut self) -> Result<(), ZipError> {
+        if let Some(started_file) = self.started_file.take() {
+            let content = started_file.content.finish()
+                .and_then(|content| content.into_reader())
+                .map_err(ZipError::from)?;
+
+            self.add_reader(&started_file.item_path, content, &started_file.options)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_file<R>(&mut self, item_path: &str, reader: R, options: &EntryOptions, modified_time: SystemTime, unix_permissions: Option<u32>) -> Result<(), ZipError>
+    where R: Read {
+        self.finish_file()?;
+
+        let compression_method = options.compression_method();
+        let codec = self.codec_registry.encoder(compression_method)
+            .ok_or_else(|| unsupported_compression(compression_method))?;
+        let encryption = options.encryption();
+        if encryption.is_some() && options.password().is_none() {
+            return Err(ZipError::new(ZipErrorKind::PasswordRequired));
+        }
+
+        let mut zip_item = ZipWriter::<W>::new_zip_item(String::from(item_path), compression_method, modified_time, unix_permissions, options)?;
+        zip_item.update_encryption(encryption);
+
+        let mut checksum_reader = ChecksumReader::new(reader);
+
+        // The CRC32 is part of the ZipCrypto encryption header, so the content is read once to calculate it before it is encrypted.
+        // Otherwise the CRC32 and the size are calculated while compressing, the local file header is updated afterwards.
+        let buffered_content = match encryption {
+            Some(ZipEncryption::ZipCrypto) => {
+                let mut content_writer = CompressedContentWriter::new();
+                std::io::copy(&mut checksum_reader, &mut content_writer).map_err(ZipError::from)?;
+                zip_item.update_crc32(checksum_reader.checksum());
+                Some(content_writer.finish().map_err(ZipError::from)?)
+            },
+            _ => None
+        };
+
+        let compression_options = options.compression_options();
+        let cdfh = ZipFile::write_zip_item(&mut self.writer, &mut zip_item, |writer, zip_item| {
+            {
+                let mut content_reader: Box<dyn BufRead> = match buffered_content {
+                    Some(buffered_content) => Box::new(BufReader::new(buffered_content.into_reader().map_err(ZipError::from)?)),
+                    None => Box::new(BufReader::new(&mut checksum_reader))
+                };
+
+                encode_encrypted(codec, &mut content_reader, writer, encryption, options.password(), zip_item.crc32(), &compression_options)?;
+            }
+
+            zip_item.update_crc32(checksum_reader.checksum());
+            let uncompressed_size = without_zip64(checksum_reader.read_size(), "The uncompressed size")
+                .map_err(|err| err.at_item(zip_item))?;
+            zip_item.update_uncompressed_size(uncompressed_size);
+            Ok(())
+        })?;
+        self.cdfh_vec.push(cdfh);
+
+        Ok(())
+    }
+
+    fn new_zip_item(item_path: String, compression_method: CompressionMethod, modified_time: SystemTime, unix_permissions: Option<u32>, options: &EntryOptions) -> Result<ZipItem, ZipError> {
+        let modified_date_time = ZipDateTime::from_system_time(modified_time).map_err(ZipError::from)?;
+
+        let mut zip_item = ZipItem::new(compression_method, item_path, 0, 0, modified_date_time, 0, EncryptionMethod::NoEncryption, 0);
+        zip_item.update_unix_permissions(unix_permissions);
+        zip_item.update_comment(String::from(options.comment()));
+
+        Ok(zip_item)
+    }
+}
+
+impl<W> Write for ZipWriter<W> where W: Write + Seek {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.started_file {
+            Some(started_file) => started_file.content.write(buf),
+            None => Err(Error::new(ErrorKind::InvalidInput, "No file is started."))
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.started_file {
+            Some(started_file) => started_file.content.flush(),
+            None => Ok(())
+        }
+    }
+}
+
+fn encode_encrypted<W>(codec: &dyn Codec, reader: &mut dyn BufRead, writer: &mut W, encryption: Option<ZipEncryption>, password: Option<&str>, crc32: u32, compression_options: &CompressionOptions) -> Result<(), ZipError>
+where W: Write {
+    let encryption = match encryption {
+        Some(encryption) => encryption,
+        None => return codec.encode(reader, writer, compression_options).map(|_| ()).map_err(ZipError::from)
+    };
+    let password = password.ok_or_else(|| ZipError::new(ZipErrorKind::PasswordRequired))?;
+
+    match encryption {
+        ZipEncryption::ZipCrypto => {
+            let mut zip_crypto_writer = ZipCryptoWriter::new(writer, password, crc32)?;
+            codec.encode(reader, &mut zip_crypto_writer, compression_options).map_err(ZipError::from)?;
+        },
+        ZipEncryption::WinZipAes(strength) => {
+            let mut winzip_aes_writer = WinZipAesWriter::new(writer, password, strength)?;
+            codec.encode(reader, &mut winzip_aes_writer, compression_options).map_err(ZipError::from)?;
+            winzip_aes_writer.finish().map_err(ZipError::from)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn unix_permissions(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn unix_permissions(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::options::{AesStrength, ExtractOptions, Password};
+
+    #[test]
+    fn written_entries_are_read_back() {
+        let mut stored = EntryOptions::new(CompressionMethod::NoCompression, None);
+        stored.set_unix_permissions(0o640);
+        stored.set_comment(String::from("stored as it is"));
+        let mut encrypted = EntryOptions::default();
+        encrypted.set_encryption(ZipEncryption::WinZipAes(AesStrength::Aes256), Password::new(String::from("secret")));
+        let mut zip_crypto = EntryOptions::default();
+        zip_crypto.set_encryption(ZipEncryption::ZipCrypto, Password::new(String::from("secret")));
+
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.add_directory("docs", &EntryOptions::default()).unwrap();
+        zip_writer.add_bytes("docs/stored.txt", b"Hello ruzito", &stored).unwrap();
+        zip_writer.start_file("docs/written.txt", &EntryOptions::default()).unwrap()
+            .write_all(b"Written with the ZipWriter").unwrap();
+        zip_writer.add_reader("aes.txt", Cursor::new(b"Encrypted with AES"), &encrypted).unwrap();
+        zip_writer.add_reader("zip_crypto.txt", Cursor::new(b"Encrypted with ZipCrypto"), &zip_crypto).unwrap();
+        let archive = zip_writer.finish().unwrap().into_inner();
+
+        let mut zip_file = ZipFile::from_reader(Cursor::new(archive)).unwrap();
+        let item_paths: Vec<&str> = zip_file.iter().map(|item| item.item_path().as_str()).collect();
+        assert_eq!(item_paths, vec!["docs/", "docs/stored.txt", "docs/written.txt", "aes.txt", "zip_crypto.txt"]);
+
+        let stored_item = zip_file.iter().nth(1).unwrap();
+        assert_eq!(stored_item.unix_permissions(), Some(0o640));
+        assert_eq!(stored_item.comment(), "stored as it is");
+        assert!(zip_file.test_all(vec![Password::new(String::from("secret"))], None).unwrap().iter().all(|(_, result)| result.is_ok()));
+
+        let dest_dir = tempfile::tempdir().unwrap();
+        zip_file.extract_all(ExtractOptions::new(dest_dir.path(), vec![Password::new(String::from("secret"))], None, 1)).unwrap();
+        assert_eq!(std::fs::read_to_string(dest_dir.path().join("docs/written.txt")).unwrap(), "Written with the ZipWriter");
+        assert_eq!(std::fs::read_to_string(dest_dir.path().join("zip_crypto.txt")).unwrap(), "Encrypted with ZipCrypto");
+    }
+
+    #[test]
+    fn writing_without_started_file_fails() {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        assert!(zip_writer.write_all(b"content").is_err());
+    }
+
+    #[test]
+    fn too_many_items_need_zip64() {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for index in 0..=u16::MAX as usize {
+            zip_writer.add_directory(&format!("{}", index), &EntryOptions::default()).unwrap();
+        }
+
+        let err = zip_writer.finish().unwrap_err();
+        assert_eq!(err.kind(), ZipErrorKind::Zip64Required);
+    }
+}
@@ -1,6 +1,6 @@
-use std::{fs::File, io::{BufReader, BufWriter, Read, Seek, SeekFrom}, path::Path};
+use std::{fs::File, io::{BufWriter, Read, Seek, SeekFrom, Write}, path::{Component, Path}};
 
-use super::{ExtractError, compression_decoder, date_time::ZipDateTime, encryption::zip_crypto::{ZipCryptoReader, ZipCryptoError}, local_file_header::LocalFileHeader, mem_map::{CompressionMethod, EncryptionMethod}, options::ExtractOptions};
+use super::{ZipError, ZipErrorKind, cancellation::CancellableWriter, codec::{CodecRegistry, DecodeInfo}, date_time::ZipDateTime, encryption::{winzip_aes::{AesExtraField, AE_2_VENDOR_VERSION, WinZipAesReader}, zip_crypto::ZipCryptoReader}, local_file_header::LocalFileHeader, progress::ProgressWriter, mem_map::{COMPRESSION_FLAGS_MASK, CompressionMethod, EncryptionMethod, LZMA_EOS_MARKER_FLAG, ZipVersion}, options::{ExtractOptions, ZipEncryption}};
 
 #[derive(Debug)]
 pub struct ZipItem {
@@ -12,10 +12,16 @@ pub struct ZipItem {
     modified_date_time: ZipDateTime,
     start_offset: u32,
     encryption_method: EncryptionMethod,
-    crc32: u32
+    crc32: u32,
+    aes_extra_field: Option<AesExtraField>,
+    unix_permissions: Option<u32>,
+    comment: String,
+    // The compression flags read from the ZIP file, new items get the flags of their compression method.
+    compression_flags: Option<u16>
 }
 impl ZipItem {
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(compression_method: CompressionMethod, item_path: String, uncompressed_size: u32, compressed_size: u32, modified_date_time: ZipDateTime, start_offset: u32, encryption_method: EncryptionMethod, crc32: u32) -> Self {
         let is_file = !item_path.ends_with("/"); 
         Self {
@@ -27,7 +33,11 @@ impl ZipItem {
             modified_date_time,
             start_offset,
             encryption_method,
-            crc32
+            crc32,
+            aes_extra_field: None,
+            unix_permissions: None,
+            comment: String::new(),
+            compression_flags: None
         }
     }
 
@@ -59,84 +69,242 @@ impl ZipItem {
         self.encryption_method
     }
 
-    pub fn extract(&self, options: &ExtractOptions) -> Result<Box<dyn AsRef<Path>>, ExtractError> {
+    pub fn aes_extra_field(&self) -> Option<&AesExtraField> {
+        self.aes_extra_field.as_ref()
+    }
+
+    // Permission bits of the item on Unix, e.g. 0o755.
+    pub fn unix_permissions(&self) -> Option<u32> {
+        self.unix_permissions
+    }
+
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    pub(crate) fn compression_flags(&self) -> Option<u16> {
+        self.compression_flags
+    }
+
+    // The compression method written to the headers, AES encrypted items keep their actual method in the extra field.
+    pub fn header_compression_method(&self) -> CompressionMethod {
+        if self.aes_extra_field.is_some() { CompressionMethod::Aex } else { self.compression_method }
+    }
+
+    pub fn header_crc32(&self) -> u32 {
+        if self.has_crc32() { self.crc32 } else { 0 }
+    }
+
+    pub fn version_needed_to_extract(&self) -> ZipVersion {
+        let version_needed_to_extract = self.compression_method.version_needed_to_extract();
+
+        // WinZip AES needs 5.1
+        if self.aes_extra_field.is_some() && version_needed_to_extract.to_byte() < 51 {
+            ZipVersion::new(5, 1)
+        } else {
+            version_needed_to_extract
+        }
+    }
+
+    pub fn general_purpose_flag(&self) -> u16 {
+        let encryption_flag = if self.encryption_method != EncryptionMethod::NoEncryption { 0x01 } else { 0x00 };
+        let compression_flags = self.compression_flags.unwrap_or_else(||
+            if self.compression_method == CompressionMethod::LZMA { LZMA_EOS_MARKER_FLAG } else { 0x00 });
+
+        encryption_flag | compression_flags
+    }
+
+    pub fn extra_field(&self) -> Vec<u8> {
+        self.aes_extra_field
+            .map(|aes_extra_field| aes_extra_field.to_binary())
+            .unwrap_or_default()
+    }
+
+    // Whether the CRC32 of the item can be checked, AE-2 encrypted items store none.
+    pub fn has_crc32(&self) -> bool {
+        self.aes_extra_field.is_none_or(|aes_extra_field| aes_extra_field.has_crc32())
+    }
+
+    pub fn extract<R>(&self, options: &ExtractOptions, zip_password: Option<&str>, codec_registry: &CodecRegistry, zip_file_reader: &mut R) -> Result<Box<dyn AsRef<Path>>, ZipError>
+    where R: Read + Seek {
+        self.check_supported(codec_registry)?;
 
         let dest_path = Path::new(options.destination_path());
 
         let dest_path = if dest_path.is_dir() { dest_path } 
             else if dest_path.is_file() { dest_path.parent().unwrap() } // Unwrap is safe here. We check whether file exist at first.
-            else { return Err(ExtractError::InvalidParentPath(format!("{}", dest_path.display()))) };
+            else { return Err(ZipError::with_cause(ZipErrorKind::InvalidPath, format!("The destination {} does not exist.", dest_path.display()))) };
 
         let item_path = Some(&self.item_path)
             .filter(|_| cfg!(windows))
             .map(|p| p.replace("/", r"\"))
             .unwrap_or(String::from(&self.item_path));
-        let item_extract_dest_path = Path::new(dest_path).join(item_path);
 
-        if options.verbose_mode() {
-            println!("{}", item_extract_dest_path.display());
+        let escapes_destination = Path::new(&item_path).components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+        if escapes_destination {
+            return Err(ZipError::with_cause(ZipErrorKind::UnsafePath, format!("{} points outside of {}.", self.item_path, dest_path.display())));
         }
 
+        let item_extract_dest_path = Path::new(dest_path).join(item_path);
+
         if !self.is_file() {
-            match std::fs::create_dir_all(item_extract_dest_path.clone()) {
-                Ok(_) =>  return Ok(Box::new(item_extract_dest_path)),
-                Err(_) => return Err(ExtractError::CreateDirError(format!("{}", &item_extract_dest_path.display())))
-            }
+            std::fs::create_dir_all(item_extract_dest_path.clone()).map_err(ZipError::from)?;
+            Ok(Box::new(item_extract_dest_path))
         } else {
-            let zip_file = File::open(options.zip_file_path()).map_err(|err| ExtractError::IOError(err))?;
             // Check if parent folder is created
             let output_file_parent_path = Path::new(&item_extract_dest_path).parent();
             match output_file_parent_path {
                 Some(path) => {
                     if !path.exists() {
-                        if let Err(_) = std::fs::create_dir_all(path) {
-                            return Err(ExtractError::CreateDirError(format!("{}", &item_extract_dest_path.display())))
-                        }
+                        std::fs::create_dir_all(path).map_err(ZipError::from)?;
                     }
                 },
-                None => return Err(ExtractError::CreateDirError(format!("{}", &item_extract_dest_path.display())))
+                None => return Err(ZipError::with_cause(ZipErrorKind::InvalidPath, format!("{} has no parent folder.", item_extract_dest_path.display())))
+            }
+            let output_file = File::create(item_extract_dest_path.clone()).map_err(ZipError::from)?;
+            let output_file = CancellableWriter::new(output_file, options.cancellation_token());
+            let mut buf_writer = BufWriter::new(ProgressWriter::new(output_file, options.progress_observer()));
+            let result = self.decompress_to(zip_password, codec_registry, zip_file_reader, &mut buf_writer)
+                .and_then(|_| buf_writer.flush().map_err(ZipError::from));
+
+            if let Err(err) = result {
+                // A cancelled item is removed instead of being left half written.
+                if err.kind() == ZipErrorKind::Cancelled {
+                    drop(buf_writer);
+                    let _ = std::fs::remove_file(&item_extract_dest_path);
+                }
+                return Err(err);
             }
-            let output_file = File::create(item_extract_dest_path.clone()).map_err(|_| ExtractError::FileCreationFailed)?;
-            let mut zip_file_reader = BufReader::new(zip_file);
-            let mut buf_writer = BufWriter::new(output_file);
-            let file_start_offset = self.start_offset();
-            zip_file_reader.seek(SeekFrom::Start(file_start_offset as u64)).map_err(|_| ExtractError::UnableToSeekZipItem(file_start_offset))?;
-            let local_file_header = LocalFileHeader::from_reader(&mut zip_file_reader).map_err(|err| ExtractError::IOError(err))?;
-            let content_start_offset = local_file_header.content_start_offset();
-                
-            zip_file_reader.seek(SeekFrom::Start(content_start_offset)).map_err(|_| ExtractError::UnableToSeekZipItem(file_start_offset))?;
-            let file_size = if local_file_header.compression_method() == CompressionMethod::NoCompression 
-                && local_file_header.encryption_method() != &EncryptionMethod::ZipCrypto { self.uncompressed_size() as u64 } else { self.compressed_size() as u64 };
-
-            let mut decompression_reader: Box<dyn Read> = match local_file_header.encryption_method() {
-               EncryptionMethod::NoEncryption => Box::new(zip_file_reader.take(file_size)),
-               EncryptionMethod::ZipCrypto => { 
-                   let zip_password = match options.zip_password() {
-                       Some(pass) => pass.clone(),
-                       None => return Err(ExtractError::ZipCryptoError(ZipCryptoError::InvalidPassword(String::from("Unknown Password."))))
-                   };
-                   let content_reader = zip_file_reader.take(file_size);
-                   let zip_crypto_reader = ZipCryptoReader::new(zip_password, local_file_header.crc32(), content_reader);
-                   match zip_crypto_reader {
-                       Ok(reader) => Box::new(reader),
-                       Err(err) => return Err(ExtractError::ZipCryptoError(err))
-                   }
-                },
-                _ => Box::new(zip_file_reader.take(file_size))
-            };
-            compression_decoder::CompressionDecoder::decode_to_file(&local_file_header.compression_method(), 
-                    &mut decompression_reader, 
-                    &mut buf_writer)
-                        .map_err(|err| ExtractError::IOError(err))?;
             Ok(Box::new(item_extract_dest_path))
         }
     }
 
-    pub fn update_compressed_size(&mut self, compressed_size: u32) {
+    // Checks that the item can be decrypted and decompressed, so that unsupported items are rejected before any output is created.
+    pub fn check_supported(&self, codec_registry: &CodecRegistry) -> Result<(), ZipError> {
+        match self.encryption_method {
+            EncryptionMethod::StrongEncryption => return Err(ZipError::with_cause(ZipErrorKind::UnsupportedEncryption, format!("{:?}", self.encryption_method))),
+            EncryptionMethod::WinZipAesEncryption if self.aes_extra_field.is_none() => return Err(ZipError::with_cause(ZipErrorKind::InvalidArchive, "The AES extra field is missing.")),
+            _ => ()
+        }
+
+        if self.is_file && codec_registry.codec(self.compression_method).is_none() {
+            return Err(unsupported_compression(self.compression_method));
+        }
+
+        Ok(())
+    }
+
+    // Decrypts and decompresses the content of the item into the given writer.
+    pub fn decompress_to<R, W>(&self, zip_password: Option<&str>, codec_registry: &CodecRegistry, zip_file_reader: &mut R, writer: &mut W) -> Result<(), ZipError>
+    where R: Read + Seek, W: Write {
+        self.check_supported(codec_registry)?;
+        let codec = codec_registry.codec(self.compression_method)
+            .ok_or_else(|| unsupported_compression(self.compression_method))?;
+        let (mut decompression_reader, general_purpose_flag) = self.content_reader(zip_password, zip_file_reader)?;
+
+        let decode_info = DecodeInfo::new(general_purpose_flag, self.uncompressed_size() as u64);
+        codec.decode(&mut decompression_reader, writer, &decode_info)
+            .map_err(ZipError::from)?;
+        // Codecs may stop before the end of the content, the authentication code of AES encrypted items is only checked once it is read.
+        std::io::copy(&mut decompression_reader, &mut std::io::sink())
+            .map_err(ZipError::from)?;
+        Ok(())
+    }
+
+    // Checks the password against the encryption header of the item, without decrypting its content.
+    pub fn check_password<R>(&self, zip_password: Option<&str>, zip_file_reader: &mut R) -> Result<(), ZipError>
+    where R: Read + Seek {
+        self.content_reader(zip_password, zip_file_reader).map(|_| ())
+    }
+
+    // Seeks to the content of the item, returns a reader decrypting it and the general purpose flag of its local file header.
+    pub(crate) fn content_reader<'r, R>(&self, zip_password: Option<&str>, mut zip_file_reader: R) -> Result<(Box<dyn Read + 'r>, u16), ZipError>
+    where R: Read + Seek + 'r {
+        let file_start_offset = self.start_offset();
+        zip_file_reader.seek(SeekFrom::Start(file_start_offset as u64)).map_err(ZipError::from)?;
+        let local_file_header = LocalFileHeader::from_reader(&mut zip_file_reader).map_err(ZipError::from)?;
+        let content_start_offset = local_file_header.content_start_offset();
+            
+        zip_file_reader.seek(SeekFrom::Start(content_start_offset)).map_err(ZipError::from)?;
+        let file_size = if local_file_header.compression_method() == CompressionMethod::NoCompression 
+            && local_file_header.encryption_method() != &EncryptionMethod::ZipCrypto { self.uncompressed_size() as u64 } else { self.compressed_size() as u64 };
+
+        let content_reader: Box<dyn Read + 'r> = match local_file_header.encryption_method() {
+           EncryptionMethod::NoEncryption => Box::new(zip_file_reader.take(file_size)),
+           EncryptionMethod::ZipCrypto => { 
+               let zip_password = match zip_password {
+                   Some(pass) => pass,
+                   None => return Err(ZipError::new(ZipErrorKind::PasswordRequired))
+               };
+               let content_reader = zip_file_reader.take(file_size);
+               let zip_crypto_reader = ZipCryptoReader::new(zip_password, local_file_header.crc32(), content_reader);
+               Box::new(zip_crypto_reader?)
+            },
+            EncryptionMethod::WinZipAesEncryption => {
+                let zip_password = match zip_password {
+                    Some(pass) => pass,
+                    None => return Err(ZipError::new(ZipErrorKind::PasswordRequired))
+                };
+                let aes_extra_field = AesExtraField::from_extra_field(local_file_header.extra_field())
+                    .ok_or_else(|| ZipError::with_cause(ZipErrorKind::InvalidArchive, "The AES extra field is missing."))?;
+                let content_reader = zip_file_reader.take(file_size);
+                let winzip_aes_reader = WinZipAesReader::new(zip_password, aes_extra_field.strength(), file_size, content_reader)?;
+                Box::new(winzip_aes_reader)
+            },
+            EncryptionMethod::StrongEncryption => return Err(ZipError::with_cause(ZipErrorKind::UnsupportedEncryption, format!("{:?}", EncryptionMethod::StrongEncryption)))
+        };
+
+        Ok((content_reader, local_file_header.general_purpose_flag()))
+    }
+
+    pub(crate) fn update_compression_method(&mut self, compression_method: CompressionMethod) {
+        if compression_method != self.compression_method {
+            self.compression_flags = None;
+        }
+        self.compression_method = compression_method;
+        self.aes_extra_field = self.aes_extra_field
+            .map(|aes_extra_field| AesExtraField::new(aes_extra_field.vendor_version(), aes_extra_field.strength(), compression_method));
+    }
+
+    pub(crate) fn update_aes_extra_field(&mut self, aes_extra_field: Option<AesExtraField>) {
+        self.aes_extra_field = aes_extra_field;
+    }
+
+    // AES encrypted items are written as AE-2.
+    pub(crate) fn update_encryption(&mut self, encryption: Option<ZipEncryption>) {
+        self.encryption_method = encryption.map_or(EncryptionMethod::NoEncryption, ZipEncryption::encryption_method);
+        self.aes_extra_field = match encryption {
+            Some(ZipEncryption::WinZipAes(strength)) => Some(AesExtraField::new(AE_2_VENDOR_VERSION, strength, self.compression_method)),
+            _ => None
+        };
+    }
+
+    pub(crate) fn update_crc32(&mut self, crc32: u32) {
+        self.crc32 = crc32;
+    }
+
+    pub(crate) fn update_uncompressed_size(&mut self, uncompressed_size: u32) {
+        self.uncompressed_size = uncompressed_size;
+    }
+
+    pub(crate) fn update_unix_permissions(&mut self, unix_permissions: Option<u32>) {
+        self.unix_permissions = unix_permissions;
+    }
+
+    pub(crate) fn update_comment(&mut self, comment: String) {
+        self.comment = comment;
+    }
+
+    pub(crate) fn update_compression_flags(&mut self, compression_flags: Option<u16>) {
+        self.compression_flags = compression_flags.map(|compression_flags| compression_flags & COMPRESSION_FLAGS_MASK);
+    }
+
+    pub(crate) fn update_compressed_size(&mut self, compressed_size: u32) {
         self.compressed_size = compressed_size;
     }
 
-    pub fn update_start_offset(&mut self, start_offset: u32) {
+    pub(crate) fn update_start_offset(&mut self, start_offset: u32) {
         self.start_offset = start_offset;
     }
 
@@ -146,4 +314,7 @@ impl ZipItem {
     pub fn start_offset(&self) -> u32 {
         self.start_offset
     }
+}
+pub(crate) fn unsupported_compression(compression_method: CompressionMethod) -> ZipError {
+    ZipError::with_cause(ZipErrorKind::UnsupportedCompression, format!("{:?}", compression_method))
 }
\ No newline at end of file
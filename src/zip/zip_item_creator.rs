@@ -1,34 +1,32 @@
-use std::{ffi::{OsStr, OsString}, fs::{Metadata, read_dir}, io::{Error, ErrorKind}, path::PathBuf, time::SystemTime};
+use std::{ffi::{OsStr, OsString}, fs::{Metadata, read_dir}, io::Error, path::{Path, PathBuf}};
 
-use chrono::{DateTime, Datelike, Local, Timelike};
-
-use super::{ZipCreatorError, crc32::calculate_checksum, date_time::ZipDateTime, mem_map::CompressionMethod, zip_item::ZipItem};
+use super::{ZipError, ZipErrorKind, without_zip64, options::{CompressionOptions, EncryptionOptions}, crc32::calculate_checksum, date_time::ZipDateTime, mem_map::CompressionMethod, zip_item::ZipItem};
 use super::mem_map::EncryptionMethod;
 
-const MIN_SIZE_TO_COMPRESS: u64 = 10000;
-
 pub struct ZipItemCreator<'a>{
-    base_path: &'a PathBuf
+    base_path: &'a PathBuf,
+    compression_options: &'a CompressionOptions
 }
 
 impl<'a> ZipItemCreator<'a> {
 
-    pub fn new(base_path: &'a PathBuf) -> Self {
+    pub fn new(base_path: &'a PathBuf, compression_options: &'a CompressionOptions) -> Self {
         Self {
-            base_path
+            base_path,
+            compression_options
         }
     }
 
-    pub fn create_zip_items(&self, path: &PathBuf, item_path: Option<&OsStr>, zip_items: &mut Vec<ZipItem>, encryption_method: EncryptionMethod) -> Result<(), ZipCreatorError> {
+    pub fn create_zip_items(&self, path: &PathBuf, item_path: Option<&OsStr>, zip_items: &mut Vec<ZipItem>, encryption_options: Option<&EncryptionOptions>) -> Result<(), ZipError> {
 
         if path.is_dir() {
            if let Some(it_path) = item_path {
 
-               let mut zip_item_path = OsString::from(it_path).into_string().map_err(|os_string| ZipCreatorError::InvalidPath(os_string))?.replace(r"\", "/");                 
+               let mut zip_item_path = OsString::from(it_path).into_string().map_err(invalid_item_path)?.replace(r"\", "/");                 
                zip_item_path.push('/');
 
                let directory = std::fs::metadata(path)
-                    .map_err(|err| ZipCreatorError::IOError(err))?;
+                    .map_err(ZipError::from)?;
             
                zip_items.push(ZipItem::new(
                    CompressionMethod::NoCompression, 
@@ -36,61 +34,68 @@ impl<'a> ZipItemCreator<'a> {
                    0, 
                    0, 
                    self.get_file_modified_date_time(&directory)
-                    .map_err(|err| ZipCreatorError::IOError(err))?, 
+                    .map_err(ZipError::from)?, 
                    0, 
                    EncryptionMethod::NoEncryption,
                    0)
                )
            }
 
-           let dir_content = read_dir(path).map_err(|err| ZipCreatorError::IOError(err))?;
+           let dir_content = read_dir(path).map_err(ZipError::from)?;
 
            for entry in dir_content {
                 let entry = match entry {
                     Ok(entry) => entry,
-                    Err(err) => return Err(ZipCreatorError::IOError(err))
+                    Err(err) => return Err(ZipError::from(err))
                 };
 
                 let entry_path = entry.path();
                 let item_path = entry_path.strip_prefix(self.base_path)
-                    .map_err(|_| ZipCreatorError::InvalidPath(OsString::from("Unable to apply strip prefix!")))?;
+                    .map_err(|err| ZipError::with_cause(ZipErrorKind::InvalidPath, err))?;
                 
-                self.create_zip_items(&entry_path, Some(item_path.as_os_str()), zip_items, encryption_method)?;
+                self.create_zip_items(&entry_path, Some(item_path.as_os_str()), zip_items, encryption_options)?;
            }
 
         }
         else {
 
             if !path.is_file() {
-                return Err(ZipCreatorError::InvalidPath(OsString::from(format!("The path {} does not exist!", path.display()))));
+                return Err(ZipError::with_cause(ZipErrorKind::InvalidPath, format!("The path {} does not exist.", path.display())));
             }
 
             let item_path = match item_path {
                 Some(path) => path,
-                None => path.file_name().unwrap()
+                None => path.file_name()
+                    .ok_or_else(|| ZipError::with_cause(ZipErrorKind::InvalidPath, format!("The path {} has no file name.", path.display())))?
             };
-            let file_metadata = std::fs::metadata(path).map_err(|err| ZipCreatorError::IOError(err))?;
+            let file_metadata = std::fs::metadata(path).map_err(ZipError::from)?;
 
             let file_size = file_metadata.len();
-            let compression_method = if file_size > MIN_SIZE_TO_COMPRESS {
-                CompressionMethod::Deflate
-            }
-            else {
-                CompressionMethod::NoCompression
+            let compression_method = match self.compression_options.compression_method() {
+                Some(compression_method) => compression_method,
+                None if self.has_store_extension(path) || !cfg!(feature = "deflate") => CompressionMethod::NoCompression,
+                None => CompressionMethod::Deflate
             };
 
-            let zip_item_path = OsString::from(item_path).into_string().map_err(|os_string| ZipCreatorError::InvalidPath(os_string))?.replace(r"\", "/");
+            let zip_item_path = OsString::from(item_path).into_string().map_err(invalid_item_path)?.replace(r"\", "/");
+            let encryption = encryption_options
+                .filter(|encryption_options| encryption_options.encrypts(&zip_item_path))
+                .map(EncryptionOptions::encryption);
 
-            zip_items.push(ZipItem::new(
+            let uncompressed_size = without_zip64(file_size, "The size of the file")
+                .map_err(|err| err.at_path(&zip_item_path))?;
+            let mut zip_item = ZipItem::new(
                 compression_method,
                 zip_item_path,
-                file_size as u32,
+                uncompressed_size,
                 0,
-                self.get_file_modified_date_time(&file_metadata).map_err(|err| ZipCreatorError::IOError(err))?,
+                self.get_file_modified_date_time(&file_metadata).map_err(ZipError::from)?,
                 0,
-                encryption_method,
-                calculate_checksum(path).map_err(|err| ZipCreatorError::IOError(err))?
-            ));
+                EncryptionMethod::NoEncryption,
+                calculate_checksum(path).map_err(ZipError::from)?
+            );
+            zip_item.update_encryption(encryption);
+            zip_items.push(zip_item);
             
         }
 
@@ -98,23 +103,17 @@ impl<'a> ZipItemCreator<'a> {
     }
 
     fn get_file_modified_date_time(&self, metadata: &Metadata) -> Result<ZipDateTime, Error> {
-
-        let modified = metadata.modified()?;
-        let modified_date_time = SystemTime::UNIX_EPOCH + modified.duration_since(SystemTime::UNIX_EPOCH)
-            .map_err(|err| Error::new(ErrorKind::Interrupted, err))?;
-
-        let datetime = DateTime::<Local>::from(modified_date_time);
-
-        let day = datetime.date().day();
-        let month = datetime.date().month();
-        let year = datetime.date().year();
-
-        let hour = datetime.time().hour();
-        let minutes = datetime.time().minute();
-        let seconds = datetime.time().second();
-
-        Ok(ZipDateTime::new(day as u8, month as u8, year as u16, hour as u8, minutes as u8, seconds as u8))
+        ZipDateTime::from_system_time(metadata.modified()?)
     }
 
+    fn has_store_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| self.compression_options.is_store_extension(extension))
+            .unwrap_or(false)
+    }
 
-}
\ No newline at end of file
+}
+fn invalid_item_path(item_path: OsString) -> ZipError {
+    ZipError::with_cause(ZipErrorKind::InvalidPath, format!("{} is not valid UTF-8.", item_path.to_string_lossy()))
+}
@@ -1,46 +1,228 @@
+use std::fs::File;
 use std::io::{BufReader, Error, SeekFrom};
 use std::io::prelude::*;
+use std::path::Path;
 
+use super::ZipError;
+use super::codec::CodecRegistry;
+use super::entry_reader::ZipEntryReader;
 use super::eof_central_dir::{MIN_EOF_CENTRAL_DIRECTORY_SIZE, EndOfCentralDirectory};
-use super::central_dir_file_header::CentralDirectoryFileHeader;
-use std::path::Path;
-use std::fs::File;
+use super::central_dir_file_header::{MIN_CENTRAL_DIRECTORY_FILE_HEADER_SIZE, CentralDirectoryFileHeader};
+use super::zip_item::ZipItem;
 
 #[derive(Debug)]
 pub struct ZipMetadata;
 
 impl ZipMetadata {
 
-    pub fn parse<P>(file_path: P) -> Result<(EndOfCentralDirectory, Vec<CentralDirectoryFileHeader>), Error> where P: AsRef<Path> {
-        let mut file = File::open(file_path)?;
-        let end_of_central_directory = ZipMetadata::parse_eof_central_dir(&mut file)?;
-        let central_directory_file_headers = ZipMetadata::parse_central_dir_headers(file, &end_of_central_directory)?;
+    pub fn parse_eof_central_dir<R>(zip_file: &mut R) -> Result<EndOfCentralDirectory, Error> where R: Read + Seek {
+        let mut buffer = vec![0; MIN_EOF_CENTRAL_DIRECTORY_SIZE];
+
+        zip_file.seek(SeekFrom::End(-(MIN_EOF_CENTRAL_DIRECTORY_SIZE as i64)))?;
+        zip_file.read_exact(&mut buffer)?;
 
-        Ok((end_of_central_directory, central_directory_file_headers))
+        EndOfCentralDirectory::from(buffer.as_ref())
     }
 
-    fn parse_eof_central_dir(zip_file: &mut File) -> Result<EndOfCentralDirectory, Error> {
-        let mut buffer = vec![0; MIN_EOF_CENTRAL_DIRECTORY_SIZE];
+}
 
-        zip_file.seek(SeekFrom::End(-1 * (MIN_EOF_CENTRAL_DIRECTORY_SIZE as i64)))?;
-        zip_file.read_exact(&mut buffer)?;
+// Iterates the items of a ZIP file straight from its central directory.
+// Only one header is read into memory at a time, so the memory used does not grow with the number of items.
+// The headers are read up to the end of the central directory, the item count of the end of central directory only has 16 bits.
+pub struct CentralDirectoryIter<R: Read + Seek> {
+    reader: BufReader<R>,
+    file_count: u16,
+    next_cdfh_offset: u64,
+    cdfh_end_offset: u64,
+    // Reading the content of an item moves the reader away from the next header.
+    content_read: bool,
+    codec_registry: CodecRegistry
+}
+
+impl CentralDirectoryIter<File> {
+    pub fn new<P>(zip_file_path: P) -> Result<Self, ZipError>
+    where P: AsRef<Path> {
+        let zip_file = File::open(zip_file_path).map_err(ZipError::from)?;
+        CentralDirectoryIter::from_reader(zip_file)
+    }
+}
+
+impl<R> CentralDirectoryIter<R> where R: Read + Seek {
+    pub fn from_reader(mut reader: R) -> Result<Self, ZipError> {
+        let eof_central_dir = ZipMetadata::parse_eof_central_dir(&mut reader).map_err(ZipError::from)?;
 
-        Ok(match EndOfCentralDirectory::from(buffer.as_ref()) {
-            Ok(eof_directory) => eof_directory,
-            Err(err) =>  return Err(err)
+        let cdfh_start_offset = eof_central_dir.cdfh_start_offset() as u64;
+        let mut reader = BufReader::new(reader);
+        reader.seek(SeekFrom::Start(cdfh_start_offset)).map_err(ZipError::from)?;
+
+        Ok(Self {
+            reader,
+            file_count: eof_central_dir.total_num_of_central_dir(),
+            next_cdfh_offset: cdfh_start_offset,
+            cdfh_end_offset: cdfh_start_offset + eof_central_dir.size_of_central_dir() as u64,
+            content_read: false,
+            codec_registry: CodecRegistry::default()
         })
     }
-    
-    fn parse_central_dir_headers(zip_file: File, eof_central_dir: &EndOfCentralDirectory) -> Result<Vec<CentralDirectoryFileHeader>, Error> {
 
-        let mut buf_reader = BufReader::new(zip_file);
-        let central_dir_count = eof_central_dir.total_num_of_central_dir() as usize;
+    // The item count recorded in the end of central directory, it wraps around for more than 65,535 items.
+    pub fn file_count(&self) -> u16 {
+        self.file_count
+    }
 
-        buf_reader.seek(SeekFrom::Start(eof_central_dir.cdfh_start_offset() as u64))?;
+    pub fn set_codec_registry(&mut self, codec_registry: CodecRegistry) {
+        self.codec_registry = codec_registry;
+    }
+
+    // Reads the content of an item yielded by this iterator, the iteration goes on once the returned reader is dropped.
+    pub fn read_item(&mut self, item: &ZipItem) -> Result<ZipEntryReader<'_>, ZipError> {
+        self.entry_reader(item, None)
+    }
 
-        (0..central_dir_count).into_iter()
-            .map(|_| CentralDirectoryFileHeader::from_reader(&mut buf_reader))
-            .collect()
+    pub fn read_item_decrypt(&mut self, item: &ZipItem, password: &str) -> Result<ZipEntryReader<'_>, ZipError> {
+        self.entry_reader(item, Some(password))
     }
 
+    fn entry_reader(&mut self, item: &ZipItem, password: Option<&str>) -> Result<ZipEntryReader<'_>, ZipError> {
+        item.check_supported(&self.codec_registry)
+            .map_err(|err| err.at_item(item))?;
+
+        self.content_read = true;
+        let codec_registry = &self.codec_registry;
+        item.content_reader(password, &mut self.reader)
+            .and_then(|(content_reader, general_purpose_flag)| ZipEntryReader::new(item, content_reader, general_purpose_flag, codec_registry))
+            .map_err(|err| err.at_item(item))
+    }
+}
+
+impl<R> Iterator for CentralDirectoryIter<R> where R: Read + Seek {
+    type Item = Result<ZipItem, ZipError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_cdfh_offset >= self.cdfh_end_offset {
+            return None;
+        }
+
+        let cdfh_offset = self.next_cdfh_offset;
+        let cdfh = if self.content_read {
+            self.content_read = false;
+            self.reader.seek(SeekFrom::Start(cdfh_offset))
+                .and_then(|_| CentralDirectoryFileHeader::from_reader(&mut self.reader))
+        } else {
+            CentralDirectoryFileHeader::from_reader(&mut self.reader)
+        };
+
+        match cdfh.and_then(|cdfh| Ok((cdfh, self.reader.stream_position()?))) {
+            Ok((cdfh, next_cdfh_offset)) => {
+                self.next_cdfh_offset = next_cdfh_offset;
+                Some(Ok(cdfh.into()))
+            },
+            Err(err) => {
+                // The position of the next header is unknown once a header could not be read.
+                self.next_cdfh_offset = self.cdfh_end_offset;
+                Some(Err(ZipError::from(err).at_offset(cdfh_offset)))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(((self.cdfh_end_offset - self.next_cdfh_offset) / MIN_CENTRAL_DIRECTORY_FILE_HEADER_SIZE as u64) as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::{ZipErrorKind, ZipFile, options::{EntryOptions, Password, ZipEncryption}, writer::ZipWriter};
+
+    #[test]
+    fn iterates_items_of_central_directory() {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.add_directory("docs", &EntryOptions::default()).unwrap();
+        zip_writer.add_bytes("docs/readme.txt", b"Read me", &EntryOptions::default()).unwrap();
+        let archive = zip_writer.finish().unwrap().into_inner();
+
+        let central_directory = CentralDirectoryIter::from_reader(Cursor::new(archive)).unwrap();
+        assert_eq!(central_directory.file_count(), 2);
+
+        let item_paths: Vec<String> = central_directory
+            .map(|item| item.unwrap().item_path().clone())
+            .collect();
+        assert_eq!(item_paths, vec!["docs/", "docs/readme.txt"]);
+    }
+
+    #[test]
+    fn reads_items_while_iterating() {
+        let mut encrypted = EntryOptions::default();
+        encrypted.set_encryption(ZipEncryption::ZipCrypto, Password::new(String::from("secret")));
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.add_bytes("first.txt", &b"First ".repeat(100), &EntryOptions::default()).unwrap();
+        zip_writer.add_bytes("second.txt", b"Second", &encrypted).unwrap();
+        zip_writer.add_bytes("third.txt", b"Third", &EntryOptions::default()).unwrap();
+        let archive = zip_writer.finish().unwrap().into_inner();
+
+        let mut central_directory = CentralDirectoryIter::from_reader(Cursor::new(archive)).unwrap();
+        let mut contents = Vec::new();
+        while let Some(item) = central_directory.next() {
+            let item = item.unwrap();
+            let mut entry_reader = match item.item_path().as_str() {
+                "second.txt" => central_directory.read_item_decrypt(&item, "secret").unwrap(),
+                _ => central_directory.read_item(&item).unwrap()
+            };
+            let mut content = Vec::new();
+            entry_reader.read_to_end(&mut content).unwrap();
+            contents.push(content);
+        }
+
+        assert_eq!(contents, vec![b"First ".repeat(100), b"Second".to_vec(), b"Third".to_vec()]);
+    }
+
+    #[test]
+    fn iterates_past_wrapped_item_count() {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for index in 0..3 {
+            zip_writer.add_bytes(&format!("{}.txt", index), b"Item", &EntryOptions::default()).unwrap();
+        }
+        let mut archive = zip_writer.finish().unwrap().into_inner();
+        // 65,537 items are recorded as 1 in the 16 bit item counts of the end of central directory.
+        let eocd_offset = archive.len() - 22;
+        archive[eocd_offset + 8..eocd_offset + 12].copy_from_slice(&[1, 0, 1, 0]);
+
+        let central_directory = CentralDirectoryIter::from_reader(Cursor::new(archive)).unwrap();
+        assert_eq!(central_directory.file_count(), 1);
+        assert_eq!(central_directory.count(), 3);
+    }
+
+    #[test]
+    fn finds_first_item_by_path() {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.add_bytes("readme.txt", b"First", &EntryOptions::default()).unwrap();
+        zip_writer.add_bytes("notes.txt", b"Notes", &EntryOptions::default()).unwrap();
+        zip_writer.add_bytes("readme.txt", b"Second", &EntryOptions::default()).unwrap();
+        let archive = zip_writer.finish().unwrap().into_inner();
+
+        let zip_file = ZipFile::from_reader(Cursor::new(archive)).unwrap();
+
+        assert_eq!(zip_file.index_of("readme.txt"), Some(0));
+        assert_eq!(zip_file.item("notes.txt").map(ZipItem::uncompressed_size), Some(5));
+        assert_eq!(zip_file.index_of("missing.txt"), None);
+    }
+
+    #[test]
+    fn stops_after_unreadable_header() {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        zip_writer.add_bytes("readme.txt", b"Read me", &EntryOptions::default()).unwrap();
+        let mut archive = zip_writer.finish().unwrap().into_inner();
+        // Breaks the signature of the only central directory file header, 46 bytes and the item path before the 22 byte end of central directory.
+        let cdfh_start_offset = archive.len() - 22 - 56;
+        archive[cdfh_start_offset] = 0;
+
+        let mut central_directory = CentralDirectoryIter::from_reader(Cursor::new(archive)).unwrap();
+
+        let err = central_directory.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ZipErrorKind::InvalidArchive);
+        assert_eq!(err.offset(), Some(cdfh_start_offset as u64));
+        assert!(central_directory.next().is_none());
+    }
 }